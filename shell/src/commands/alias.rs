@@ -0,0 +1,162 @@
+use std::sync::Mutex;
+use crate::commands::builtin::{status, Builtin, Context, OptionSpec};
+use crate::utils::WordSplitter;
+use crate::{print_success, ALIASES};
+
+/// Aliases whose expansion is running as a list; the commands of that list
+/// must not expand them again
+static ACTIVE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The alias table and the rules for expanding a command's first word
+pub struct Aliases;
+
+impl Aliases {
+    pub fn get(name: &str) -> Option<String> {
+        ALIASES.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn set(name: &str, value: &str) {
+        ALIASES.lock().unwrap().insert(name.to_string(), value.to_string());
+    }
+
+    /// Anything but blanks, quotes, `/`, `$`, `=` and the shell's operators
+    fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || "/$`='\"\\|&;()<>".contains(c))
+    }
+
+    /// Replaces the first word of `text` when it is an alias, along with the
+    /// aliases that value starts with. A value ending in a blank makes the
+    /// word after it eligible too. No alias is expanded twice for the same
+    /// command, which stops self-recursive ones like `alias ls='ls -F'`.
+    /// Returns the new text and the aliases that were expanded.
+    pub fn expand(text: &str) -> (String, Vec<String>) {
+        let mut used = ACTIVE.lock().unwrap().clone();
+        let skip = used.len();
+        let text = Self::expand_word(text, &mut used);
+        (text, used.split_off(skip))
+    }
+
+    fn expand_word(text: &str, used: &mut Vec<String>) -> String {
+        let start = text.len() - text.trim_start().len();
+        let (lead, text) = text.split_at(start);
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        let (word, rest) = text.split_at(end);
+        let value = match Self::get(word) {
+            Some(value) if Self::is_valid_name(word) && !used.iter().any(|name| name == word) => value,
+            _ => return format!("{}{}", lead, text),
+        };
+        used.push(word.to_string());
+        let value_text = Self::expand_word(&value, used);
+        let rest = match value.ends_with(char::is_whitespace) {
+            true => Self::expand_word(rest, used),
+            false => rest.to_string(),
+        };
+        format!("{}{}{}", lead, value_text, rest)
+    }
+
+    /// Runs `f` while `names` count as being expanded
+    pub fn while_active<T>(names: &[String], f: impl FnOnce() -> T) -> T {
+        let depth = {
+            let mut active = ACTIVE.lock().unwrap();
+            let depth = active.len();
+            active.extend(names.iter().cloned());
+            depth
+        };
+        let res = f();
+        ACTIVE.lock().unwrap().truncate(depth);
+        res
+    }
+}
+
+pub struct AliasCommand;
+
+impl Builtin for AliasCommand {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn help(&self) -> &'static str {
+        "alias [-p] [name[=value] ...]\nDefine aliases, or print them when no value is given."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[OptionSpec { flag: "-p", help: "print every alias" }]
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        Ok(status(Self::alias(args), 1))
+    }
+}
+
+impl AliasCommand {
+    /// `alias [-p] [name[=value]...]`; with no names every alias is listed
+    fn alias(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.unquote()).filter(|arg| arg != "-p").collect();
+        if args.is_empty() {
+            for (name, value) in ALIASES.lock().unwrap().iter() {
+                Self::print(name, value);
+            }
+            return Ok(());
+        }
+        let mut res = Ok(());
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, _)) if !Aliases::is_valid_name(name) => {
+                    res = Err(format!("alias: `{}': invalid alias name", name).into());
+                }
+                Some((name, value)) => Aliases::set(name, value),
+                None => match Aliases::get(&arg) {
+                    Some(value) => Self::print(&arg, &value),
+                    None => res = Err(format!("alias: {}: not found", arg).into()),
+                },
+            }
+        }
+        res
+    }
+
+    fn print(name: &str, value: &str) {
+        print_success!("alias {}='{}'", name, value.replace('\'', "'\\''"));
+    }
+}
+
+pub struct UnaliasCommand;
+
+impl Builtin for UnaliasCommand {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn help(&self) -> &'static str {
+        "unalias [-a] name [name ...]\nRemove aliases."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[OptionSpec { flag: "-a", help: "remove every alias" }]
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        Ok(status(Self::unalias(args), 1))
+    }
+}
+
+impl UnaliasCommand {
+    /// `unalias -a` or `unalias name...`
+    fn unalias(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.unquote()).collect();
+        if args.is_empty() {
+            return Err("unalias: usage: unalias [-a] name [name ...]".into());
+        }
+        let mut aliases = ALIASES.lock().unwrap();
+        if args.iter().any(|arg| arg == "-a") {
+            aliases.clear();
+            return Ok(());
+        }
+        let mut res = Ok(());
+        for name in args {
+            if aliases.remove(&name).is_none() {
+                res = Err(format!("unalias: {}: not found", name).into());
+            }
+        }
+        res
+    }
+}
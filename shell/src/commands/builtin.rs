@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use crate::{print_error, CURRENT_DIR};
+
+/// A flag a builtin understands, shown by `help` and offered by completion
+pub struct OptionSpec {
+    pub flag: &'static str,
+    pub help: &'static str,
+}
+
+/// What a builtin runs with. The directory is a snapshot; builtins change
+/// it with `chdir` so the shell, this context and child processes agree.
+pub struct Context {
+    pub current_dir: PathBuf,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self { current_dir: CURRENT_DIR.lock().unwrap().clone() }
+    }
+
+    pub fn chdir(&mut self, dir: PathBuf) {
+        // children and relative command paths follow the shell's directory
+        let _ = std::env::set_current_dir(&dir);
+        *CURRENT_DIR.lock().unwrap() = dir.clone();
+        self.current_dir = dir;
+    }
+}
+
+/// A command run inside the shell. Adding one only takes an implementation
+/// and a line in `Registry::default`.
+pub trait Builtin: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The usage line, then a short description
+    fn help(&self) -> &'static str;
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[]
+    }
+
+    /// Returns the exit status: 0 on success, 1 on failure, 2 on misuse.
+    /// Errors are left for control flow such as `exit`; anything else is
+    /// reported by the interpreter like any failed command.
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>>;
+}
+
+/// Reports a failed builtin and turns its result into a status
+pub fn status(res: Result<(), Box<dyn std::error::Error>>, failure: i32) -> i32 {
+    match res {
+        Ok(_) => 0,
+        Err(err) => {
+            print_error!("{}", err.to_string());
+            failure
+        }
+    }
+}
+
+/// Builtins by name
+pub struct Registry {
+    builtins: BTreeMap<&'static str, Box<dyn Builtin>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self { builtins: BTreeMap::new() }
+    }
+
+    pub fn register(&mut self, builtin: Box<dyn Builtin>) {
+        self.builtins.insert(builtin.name(), builtin);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Builtin> {
+        self.builtins.get(name).map(|builtin| builtin.as_ref())
+    }
+
+    /// Every builtin, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Builtin> {
+        self.builtins.values().map(|builtin| builtin.as_ref())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        use crate::commands::alias::{AliasCommand, UnaliasCommand};
+        use crate::commands::cd::CdBuiltin;
+        use crate::commands::command::{BuiltinCommand, CommandCommand};
+        use crate::commands::complete::CompleteCommand;
+        use crate::commands::echo::EchoBuiltin;
+        use crate::commands::exec::ExecCommand;
+        use crate::commands::ExitCommand;
+        use crate::commands::hash::HashCommand;
+        use crate::commands::help::HelpCommand;
+        use crate::commands::highlight::HighlightCommand;
+        use crate::commands::ls::LsBuiltin;
+        use crate::commands::mkdir::MkdirBuiltin;
+        use crate::commands::set::SetCommand;
+        use crate::commands::simple::{ClearCommand, CotshCommand, PwdCommand};
+        use crate::commands::trap::TrapCommand;
+        use crate::commands::type_cmd::TypeCommand;
+
+        let mut registry = Self::new();
+        registry.register(Box::new(AliasCommand));
+        registry.register(Box::new(BuiltinCommand));
+        registry.register(Box::new(CdBuiltin));
+        registry.register(Box::new(ClearCommand));
+        registry.register(Box::new(CommandCommand));
+        registry.register(Box::new(CompleteCommand));
+        registry.register(Box::new(CotshCommand));
+        registry.register(Box::new(EchoBuiltin));
+        registry.register(Box::new(ExecCommand));
+        registry.register(Box::new(ExitCommand));
+        registry.register(Box::new(HashCommand));
+        registry.register(Box::new(HelpCommand));
+        registry.register(Box::new(HighlightCommand));
+        registry.register(Box::new(LsBuiltin));
+        registry.register(Box::new(MkdirBuiltin));
+        registry.register(Box::new(PwdCommand));
+        registry.register(Box::new(SetCommand));
+        registry.register(Box::new(TrapCommand));
+        registry.register(Box::new(TypeCommand { which: false }));
+        registry.register(Box::new(TypeCommand { which: true }));
+        registry.register(Box::new(UnaliasCommand));
+        registry
+    }
+}
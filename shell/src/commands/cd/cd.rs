@@ -1,5 +1,7 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use crate::commands::builtin::{Builtin, Context};
 use crate::commands::ls::LsCommand;
+use crate::print_error;
 use crate::utils::WordSplitter;
 
 pub struct CdCommand{
@@ -9,23 +11,23 @@ pub struct CdCommand{
 }
 
 impl CdCommand{
-    pub fn new(current_dir: &PathBuf, arg: &Vec<String>) -> Result<Self, (Box<dyn std::error::Error>, PathBuf)>{
+    pub fn new(current_dir: &Path, arg: &[String]) -> Result<Self, Box<dyn std::error::Error>>{
         if arg.len() > 1{
-            return Err(("cd: too many arguments".into(), current_dir.into()));
+            return Err("cd: too many arguments".into());
         }
-        let arg: String = if arg.len() != 0{
+        let arg: String = if !arg.is_empty(){
             arg.first().unwrap().into()
         }else{
             String::from("")      
         };
         let split_path: Vec<String> = match arg.split_path() {
             Ok(split_path) => split_path,
-            Err(_) => return Err(("cd: invalid path".into(), current_dir.into())),
+            Err(_) => return Err("cd: invalid path".into()),
         };
         let current_dir = if arg == "/"{
             PathBuf::from("/")      
         }else{
-            current_dir.clone()
+            current_dir.to_path_buf()
         };
         Ok(Self{
             current_dir,
@@ -35,7 +37,7 @@ impl CdCommand{
     }
 
     pub fn run(mut self) -> Result<PathBuf, Box<dyn std::error::Error>>{
-        if self.split_path.len() == 0 {
+        if self.split_path.is_empty() {
             return Ok(self.current_dir)
         };
         let first_path = match self.split_path.first(){
@@ -43,7 +45,7 @@ impl CdCommand{
             None => return Ok(self.current_dir),
         };
         if first_path.is_empty(){
-            if self.arg == String::from(""){
+            if self.arg.is_empty(){
                 self.current_dir = self.go_upper_dir();
             }
             return Ok(self.current_dir)       
@@ -75,7 +77,32 @@ impl CdCommand{
     }
 
 
-    fn is_valid_dir(&self, arg: &String) -> Result<bool, Box<dyn std::error::Error>>{
-        LsCommand::new(&self.current_dir, &vec![])?.is_valid_path(arg)
+    fn is_valid_dir(&self, arg: &str) -> Result<bool, Box<dyn std::error::Error>>{
+        LsCommand::new(&self.current_dir, &[])?.is_valid_path(arg)
     }
-}
\ No newline at end of file
+}
+
+pub struct CdBuiltin;
+
+impl Builtin for CdBuiltin {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn help(&self) -> &'static str {
+        "cd [dir]\nChange the shell's working directory."
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        match CdCommand::new(&ctx.current_dir, args).and_then(|cd| cd.run()) {
+            Ok(dir) => {
+                ctx.chdir(dir);
+                Ok(0)
+            }
+            Err(err) => {
+                print_error!("{}", err.to_string());
+                Ok(1)
+            }
+        }
+    }
+}
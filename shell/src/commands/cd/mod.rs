@@ -1,3 +1,3 @@
 mod cd;
 
-pub use cd::CdCommand;
\ No newline at end of file
+pub use cd::{CdBuiltin, CdCommand};
\ No newline at end of file
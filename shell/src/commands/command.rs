@@ -0,0 +1,133 @@
+use crate::commands::builtin::{Builtin, Context, OptionSpec};
+use crate::commands::commands::Commands;
+use crate::commands::executable_cmds::ExecutableCmds;
+use crate::commands::CmdParser;
+use crate::utils::WordSplitter;
+use crate::{print_error, print_success};
+
+/// Searched by `command -p` instead of `$PATH`
+const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/usr/local/sbin:/usr/sbin:/sbin";
+
+pub struct CommandCommand;
+
+impl Builtin for CommandCommand {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn help(&self) -> &'static str {
+        "command [-pVv] name [args ...]\nRun name as a builtin or from PATH, skipping aliases."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[
+            OptionSpec { flag: "-p", help: "search a default PATH" },
+            OptionSpec { flag: "-v", help: "print what name resolves to" },
+            OptionSpec { flag: "-V", help: "describe what name resolves to" },
+        ]
+    }
+
+    /// `command [-pVv] name [args...]`: runs `name` as a builtin or from
+    /// `PATH`, never as an alias or function. `-p` searches a default path,
+    /// `-v` prints what `name` resolves to and `-V` describes it.
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        let (mut default_path, mut print_path, mut describe) = (false, false, false);
+        let mut rest = args;
+        while let Some(flag) = rest.first().map(|arg| arg.unquote()) {
+            if flag == "--" {
+                rest = &rest[1..];
+                break;
+            }
+            if !flag.starts_with('-') || flag == "-" {
+                break;
+            }
+            for char in flag.chars().skip(1) {
+                match char {
+                    'p' => default_path = true,
+                    'v' => print_path = true,
+                    'V' => describe = true,
+                    _ => {
+                        print_error!("command: -{}: invalid option", char);
+                        return Ok(2);
+                    }
+                }
+            }
+            rest = &rest[1..];
+        }
+        let (name, args) = match rest.split_first() {
+            Some((name, args)) => (name.unquote(), args.to_vec()),
+            None => return Ok(0),
+        };
+        let mut cmd = Commands::new(&name)?;
+        if default_path && matches!(cmd, Commands::NotBuildIn(_) | Commands::InvalidCmd(_)) {
+            cmd = match ExecutableCmds::new_in(&name, DEFAULT_PATH) {
+                Ok(res) => Commands::NotBuildIn(res),
+                Err(_) => Commands::InvalidCmd(name.clone()),
+            };
+        }
+        if !describe && !print_path {
+            return CmdParser::run_resolved(cmd, args);
+        }
+        let status = match &cmd {
+            Commands::InvalidCmd(_) | Commands::EmptyCommand => {
+                if describe {
+                    print_error!("cotsh: command: {}: not found", name);
+                }
+                1
+            }
+            Commands::NotBuildIn(res) if describe => {
+                print_success!("{} is {}", name, res.executable_path());
+                0
+            }
+            Commands::NotBuildIn(res) => {
+                print_success!("{}", res.executable_path());
+                0
+            }
+            Commands::Plugin(plugin) if describe => {
+                print_success!("{} is a plugin ({})", name, plugin.path().display());
+                0
+            }
+            Commands::Plugin(plugin) => {
+                print_success!("{}", plugin.path().display());
+                0
+            }
+            Commands::BuildIn(_) if describe => {
+                print_success!("{} is a shell builtin", name);
+                0
+            }
+            Commands::BuildIn(_) => {
+                print_success!("{}", name);
+                0
+            }
+        };
+        Ok(status)
+    }
+}
+
+pub struct BuiltinCommand;
+
+impl Builtin for BuiltinCommand {
+    fn name(&self) -> &'static str {
+        "builtin"
+    }
+
+    fn help(&self) -> &'static str {
+        "builtin name [args ...]\nRun the shell builtin name."
+    }
+
+    /// `builtin name [args...]`: runs the builtin `name` even when a command
+    /// of that name would be found first
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        let (name, args) = match args.split_first() {
+            Some((name, args)) => (name.unquote(), args.to_vec()),
+            None => return Ok(0),
+        };
+        match Commands::new(&name)? {
+            cmd @ Commands::BuildIn(_) => CmdParser::run_resolved(cmd, args),
+            _ => {
+                print_error!("builtin: {}: not a shell builtin", name);
+                Ok(1)
+            }
+        }
+    }
+}
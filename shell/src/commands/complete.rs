@@ -0,0 +1,214 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use crate::commands::builtin::{Builtin, Context, OptionSpec};
+use crate::commands::executable_cmds::ExecutableCmds;
+use crate::utils::WordSplitter;
+use crate::{print_error, print_success, COMPLETIONS};
+
+/// The settings `complete -o` accepts
+const COMP_OPTIONS: &[&str] = &["default", "dirnames", "nospace", "plusdirs"];
+
+/// How the arguments of one command are completed, see the `complete` builtin
+#[derive(Clone, Default)]
+pub struct CompSpec {
+    /// `-a`: words offered as they are
+    pub words: Vec<String>,
+    /// `-F`: a command whose output lines are offered
+    pub function: Option<String>,
+    /// `-f`: files and directories
+    pub files: bool,
+    /// `-d`: directories
+    pub dirs: bool,
+    /// `-o`: any of `COMP_OPTIONS`
+    pub options: Vec<String>,
+}
+
+impl CompSpec {
+    pub fn get(name: &str) -> Option<Self> {
+        COMPLETIONS.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn has_option(&self, option: &str) -> bool {
+        self.options.iter().any(|set| set == option)
+    }
+
+    /// Runs the `-F` command as `func cmd word prev`, with `COMP_LINE`,
+    /// `COMP_POINT` and `COMP_CWORD` set the way bash sets them, and returns
+    /// the lines it prints. The shell has no functions, so `func` is looked up
+    /// like any command.
+    pub fn call(&self, words: &[String], line: &str, cursor: usize, dir: &Path) -> Vec<String> {
+        let function = match &self.function {
+            Some(function) => function,
+            None => return vec![],
+        };
+        let path = match ExecutableCmds::resolve(function, dir) {
+            Ok(path) => path,
+            Err(_) => return vec![],
+        };
+        let word = words.last().map(String::as_str).unwrap_or_default();
+        let prev = match words.len() {
+            len if len >= 2 => words[len - 2].as_str(),
+            _ => "",
+        };
+        let output = Command::new(path)
+            .args([words[0].as_str(), word, prev])
+            .current_dir(dir)
+            .env("COMP_LINE", line)
+            .env("COMP_POINT", cursor.to_string())
+            .env("COMP_CWORD", (words.len() - 1).to_string())
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(output) => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Prints the spec as a `complete` command that defines it again
+    fn print(&self, name: &str) {
+        let mut line = String::from("complete");
+        for option in &self.options {
+            line.push_str(&format!(" -o {}", option));
+        }
+        if self.files {
+            line.push_str(" -f");
+        }
+        if self.dirs {
+            line.push_str(" -d");
+        }
+        if !self.words.is_empty() {
+            line.push_str(&format!(" -a {}", Self::quote(&self.words.join(" "))));
+        }
+        if let Some(function) = &self.function {
+            line.push_str(&format!(" -F {}", function));
+        }
+        print_success!("{} {}", line, name);
+    }
+
+    /// `text` in single quotes, or in double quotes when it holds a single
+    /// quote, which the shell can't escape inside single quotes
+    fn quote(text: &str) -> String {
+        if !text.contains('\'') {
+            return format!("'{}'", text);
+        }
+        let mut quoted = String::from("\"");
+        for char in text.chars() {
+            if matches!(char, '\\' | '"' | '$' | '`') {
+                quoted.push('\\');
+            }
+            quoted.push(char);
+        }
+        quoted.push('"');
+        quoted
+    }
+}
+
+pub struct CompleteCommand;
+
+impl Builtin for CompleteCommand {
+    fn name(&self) -> &'static str {
+        "complete"
+    }
+
+    fn help(&self) -> &'static str {
+        "complete [-fdpr] [-o option] [-a words] [-F command] [-c] name ...\nDefine how the arguments of each name are completed, or print the definitions."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[
+            OptionSpec { flag: "-a", help: "offer the words in the list" },
+            OptionSpec { flag: "-F", help: "offer the lines command prints" },
+            OptionSpec { flag: "-f", help: "offer files" },
+            OptionSpec { flag: "-d", help: "offer directories" },
+            OptionSpec { flag: "-o", help: "default, dirnames, nospace or plusdirs" },
+            OptionSpec { flag: "-c", help: "the command to define completion for" },
+            OptionSpec { flag: "-p", help: "print the definitions" },
+            OptionSpec { flag: "-r", help: "remove the definitions" },
+        ]
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.unquote()).collect();
+        let (mut spec, mut names) = (CompSpec::default(), Vec::new());
+        let (mut print, mut remove) = (false, false);
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "-a" | "-F" | "-o" | "-c" => {
+                    let value = match rest.next() {
+                        Some(value) => value.clone(),
+                        None => {
+                            print_error!("complete: {}: option requires an argument", arg);
+                            return Ok(2);
+                        }
+                    };
+                    match arg.as_str() {
+                        "-a" => spec.words.extend(value.split_whitespace().map(String::from)),
+                        "-F" => spec.function = Some(value),
+                        "-o" if COMP_OPTIONS.contains(&value.as_str()) => spec.options.push(value),
+                        "-o" => {
+                            print_error!("complete: {}: invalid option name", value);
+                            return Ok(2);
+                        }
+                        _ => names.push(value),
+                    }
+                }
+                "--" => {
+                    names.extend(rest.by_ref().cloned());
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 => {
+                    for flag in flags.chars().skip(1) {
+                        match flag {
+                            'f' => spec.files = true,
+                            'd' => spec.dirs = true,
+                            'p' => print = true,
+                            'r' => remove = true,
+                            _ => {
+                                print_error!("complete: -{}: invalid option", flag);
+                                return Ok(2);
+                            }
+                        }
+                    }
+                }
+                name => names.push(name.to_string()),
+            }
+        }
+
+        let mut completions = COMPLETIONS.lock().unwrap();
+        if remove && names.is_empty() {
+            completions.clear();
+            return Ok(0);
+        }
+        if print || names.is_empty() {
+            let mut status = 0;
+            if names.is_empty() {
+                names = completions.keys().cloned().collect();
+            }
+            for name in names {
+                match completions.get(&name) {
+                    Some(spec) => spec.print(&name),
+                    None => {
+                        print_error!("complete: {}: no completion specification", name);
+                        status = 1;
+                    }
+                }
+            }
+            return Ok(status);
+        }
+        let mut status = 0;
+        for name in names {
+            if !remove {
+                completions.insert(name, spec.clone());
+            } else if completions.remove(&name).is_none() {
+                print_error!("complete: {}: no completion specification", name);
+                status = 1;
+            }
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeSet;
+use crate::commands::complete::CompSpec;
+use crate::commands::executable_cmds::ExecutableCmds;
+use crate::commands::ls::LsCommand;
+use crate::commands::plugin::Plugin;
+use crate::utils::DisplayWidth;
+use crate::{ALIASES, BUILTINS, COMMAND_HASH, CURRENT_DIR};
+
+/// Characters that end a word when they are not quoted
+const WORD_BREAKS: &[char] = &[' ', '\t', '<', '>'];
+/// Characters escaped with a backslash in a completed word
+const SPECIAL: &[char] = &[' ', '\t', '\'', '"', '\\', '$', '`', ';', '|', '&', '(', ')', '<', '>', '*', '?', '!'];
+
+/// One possible completion of the word under the cursor
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Candidate {
+    /// The whole word, unquoted
+    word: String,
+    /// What a listing shows: the name without its directory
+    display: String,
+    is_dir: bool,
+}
+
+impl Candidate {
+    fn word(word: String) -> Self {
+        Self { display: word.clone(), word, is_dir: false }
+    }
+}
+
+/// The completion of the word before the cursor
+pub struct Completion {
+    /// Where the word starts in the line
+    start: usize,
+    /// The word as typed, unquoted
+    typed: String,
+    /// The quote the word was opened with and is still inside of
+    quote: Option<char>,
+    /// `complete -o nospace`: no space after a unique match
+    nospace: bool,
+    candidates: Vec<Candidate>,
+}
+
+impl Completion {
+    pub fn new(line: &str, cursor: usize) -> Self {
+        Self::build(line, cursor, true)
+    }
+
+    /// Like `new`, without running `complete -F` commands and with the
+    /// `PATH` executables remembered from the last listing: for suggesting
+    /// as the line is typed
+    pub fn quiet(line: &str, cursor: usize) -> Self {
+        Self::build(line, cursor, false)
+    }
+
+    fn build(line: &str, cursor: usize, call: bool) -> Self {
+        let (start, words, quote) = Self::current_words(&line[..cursor]);
+        let typed = words.last().cloned().unwrap_or_default();
+        let (mut candidates, nospace) = match words.len() {
+            1 if !typed.contains('/') => (Self::commands(&typed, call), false),
+            1 => (Self::paths(&typed, true), false),
+            _ => Self::arguments(&words, line, cursor, call),
+        };
+        candidates.sort();
+        candidates.dedup_by(|a, b| a.word == b.word);
+        Self { start, typed, quote, nospace, candidates }
+    }
+
+    /// The words of the command the cursor is in, unquoted, ending with the
+    /// word under the cursor; where that word starts; and which quote is
+    /// still open at its end
+    fn current_words(before: &str) -> (usize, Vec<String>, Option<char>) {
+        let (mut start, mut words, mut typed) = (0, Vec::new(), String::new());
+        let (mut quote, mut escaped) = (None, false);
+        for (index, char) in before.char_indices() {
+            match (quote, char) {
+                _ if escaped => {
+                    typed.push(char);
+                    escaped = false;
+                }
+                (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+                (Some('"'), '\\') | (None, '\\') => escaped = true,
+                (Some(_), _) => typed.push(char),
+                (None, '\'') | (None, '"') => quote = Some(char),
+                // a new command starts after an operator
+                (None, ';') | (None, '|') | (None, '&') | (None, '(') => {
+                    start = index + char.len_utf8();
+                    words.clear();
+                    typed.clear();
+                }
+                (None, _) if WORD_BREAKS.contains(&char) => {
+                    start = index + char.len_utf8();
+                    if !typed.is_empty() {
+                        words.push(std::mem::take(&mut typed));
+                    }
+                }
+                (None, _) => typed.push(char),
+            }
+        }
+        words.push(typed);
+        (start, words, quote)
+    }
+
+    /// The arguments of `words[0]`: its flags when it is a builtin and the
+    /// word starts with `-`, then what its `complete` spec offers, or paths
+    /// when it has none. Also says whether the spec sets `-o nospace`. The
+    /// `-F` command only runs when `call` is set.
+    fn arguments(words: &[String], line: &str, cursor: usize, call: bool) -> (Vec<Candidate>, bool) {
+        let (name, typed) = (words[0].as_str(), words[words.len() - 1].as_str());
+        let mut candidates = Vec::new();
+        if let Some(builtin) = BUILTINS.get(name).filter(|_| typed.starts_with('-')) {
+            let flags = builtin.options().iter().map(|option| option.flag.to_string());
+            candidates.extend(flags.filter(|flag| flag.starts_with(typed)).map(Candidate::word));
+        }
+        let spec = match CompSpec::get(name) {
+            Some(spec) => spec,
+            None if candidates.is_empty() => return (Self::paths(typed, false), false),
+            None => return (candidates, false),
+        };
+        let dir = CURRENT_DIR.lock().unwrap().clone();
+        let called = match call {
+            true => spec.call(words, line, cursor, &dir),
+            false => vec![],
+        };
+        let offered = spec.words.iter().cloned().chain(called);
+        candidates.extend(offered.filter(|word| word.starts_with(typed)).map(Candidate::word));
+        let dirs = || Self::paths(typed, false).into_iter().filter(|candidate| candidate.is_dir);
+        if spec.files {
+            candidates.extend(Self::paths(typed, false));
+        } else if spec.dirs || spec.has_option("plusdirs") {
+            candidates.extend(dirs());
+        }
+        if candidates.is_empty() && spec.has_option("default") {
+            candidates = Self::paths(typed, false);
+        } else if candidates.is_empty() && spec.has_option("dirnames") {
+            candidates = dirs().collect();
+        }
+        (candidates, spec.has_option("nospace"))
+    }
+
+    /// Builtins, aliases, plugins and `PATH` executables starting with
+    /// `prefix`. `PATH` is listed again when `refresh` is set.
+    fn commands(prefix: &str, refresh: bool) -> Vec<Candidate> {
+        let mut names: BTreeSet<String> = BTreeSet::new();
+        names.extend(BUILTINS.iter().map(|builtin| builtin.name().to_string()));
+        names.extend(ALIASES.lock().unwrap().keys().cloned());
+        names.extend(Plugin::all().iter().map(|plugin| plugin.name().to_string()));
+        names.extend(COMMAND_HASH.lock().unwrap().path_commands(refresh).iter().cloned());
+        names
+            .into_iter()
+            .filter(|name| name.starts_with(prefix))
+            .map(Candidate::word)
+            .collect()
+    }
+
+    /// Files and directories starting with `prefix`, relative to the shell's
+    /// directory. In command position only directories and executables.
+    fn paths(prefix: &str, executables_only: bool) -> Vec<Candidate> {
+        let (dir_part, name_prefix) = match prefix.rfind('/') {
+            Some(index) => prefix.split_at(index + 1),
+            None => ("", prefix),
+        };
+        let dir = CURRENT_DIR.lock().unwrap().join(dir_part);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // dotfiles only when asked for
+                if !name.starts_with(name_prefix) || (name.starts_with('.') && !name_prefix.starts_with('.')) {
+                    return None;
+                }
+                let path = entry.path();
+                let is_dir = path.is_dir();
+                if executables_only && !is_dir && !ExecutableCmds::is_executable(&path) {
+                    return None;
+                }
+                Some(Candidate { word: format!("{}{}", dir_part, name), display: name, is_dir })
+            })
+            .collect()
+    }
+
+    /// Where the word being completed starts in the line
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The text to put in place of the word: the longest prefix all
+    /// candidates share, quoted, and finished with `/` or a space when there is
+    /// only one candidate. `None` when that adds nothing to the word.
+    pub fn replacement(&self) -> Option<String> {
+        let first = self.candidates.first()?;
+        let common = self.candidates.iter().skip(1).fold(first.word.as_str(), |common, candidate| {
+            let len = common
+                .char_indices()
+                .zip(candidate.word.chars())
+                .take_while(|((_, a), b)| a == b)
+                .last()
+                .map_or(0, |((index, char), _)| index + char.len_utf8());
+            &common[..len]
+        });
+        let unique = self.candidates.len() == 1;
+        if common.len() <= self.typed.len() && !unique {
+            return None;
+        }
+        let mut text = match self.quote {
+            Some(quote) => format!("{}{}", quote, common),
+            None => Self::escape(common),
+        };
+        if unique && first.is_dir {
+            text.push('/');
+        } else if unique {
+            if let Some(quote) = self.quote {
+                text.push(quote);
+            }
+            if !self.nospace {
+                text.push(' ');
+            }
+        }
+        Some(text)
+    }
+
+    /// The candidates laid out in columns for a terminal `width` wide
+    pub fn listing(&self, width: u16) -> Vec<String> {
+        let names: Vec<String> = self
+            .candidates
+            .iter()
+            .map(|candidate| match candidate.is_dir {
+                true => format!("{}/", candidate.display),
+                false => candidate.display.clone(),
+            })
+            .collect();
+        let longest = names.iter().map(|name| name.display_width()).max().unwrap_or(0);
+        LsCommand::columns(&names, longest as u16 + 2, width)
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    fn escape(word: &str) -> String {
+        let mut escaped = String::new();
+        for char in word.chars() {
+            if SPECIAL.contains(&char) {
+                escaped.push('\\');
+            }
+            escaped.push(char);
+        }
+        escaped
+    }
+}
+
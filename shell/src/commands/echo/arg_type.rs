@@ -27,7 +27,7 @@ impl EchoArg {
             text if text.starts_with('-') => Flag(text.to_string()),
             text if text.starts_with('"') && text.ends_with('"') => DoubleQuoteTxt(text.to_string()),
             text if text.starts_with('\'') && text.ends_with('\'') => QuoteTxt(text.to_string()),
-            text if text.eq(">") => WriteInto(text.to_string()),
+            text if text.eq(">") || text.eq(">|") => WriteInto(text.to_string()),
             text if text.eq(">>") => AppendInto(text.to_string()),
             _ => Plain(text.to_string())
         }
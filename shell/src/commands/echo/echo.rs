@@ -1,9 +1,11 @@
+use crate::commands::builtin::{Builtin, Context, OptionSpec};
 use crate::commands::cd::CdCommand;
 use crate::commands::echo::arg_type::EchoArg;
 use crate::utils::{WordSplitter};
+use crate::{print_error, SHELL_OPTIONS};
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct EchoCommand {
     args: Vec<EchoArg>,
@@ -16,8 +18,8 @@ pub struct EchoCommand {
 }
 
 impl EchoCommand {
-    pub fn new(args: Vec<String>, current_dir: &PathBuf) -> Self {
-        let re_dir = current_dir.clone();
+    pub fn new(args: Vec<String>, current_dir: &Path) -> Self {
+        let re_dir = current_dir.to_path_buf();
         let args = args.join(" ");
         let args = args.advance_split();
         let args: Vec<EchoArg> = args.iter().map(|arg| EchoArg::new(arg)).collect();
@@ -36,7 +38,7 @@ impl EchoCommand {
             raw_text: None,
             output_dir: re_dir,
             option,
-            current_dir: current_dir.clone(),
+            current_dir: current_dir.to_path_buf(),
             is_redirect: false,
             redir_option: None,
         }
@@ -51,15 +53,14 @@ impl EchoCommand {
 
         let temp = temp
             .iter()
-            .filter(|arg| arg.is_some())
-            .map(|arg| arg.clone().unwrap())
+            .filter_map(|arg| arg.clone())
             .collect::<Vec<String>>();
         
         temp.join(" ")
     }
 
     fn init_work(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let pre_process = format!("{}",self.combine_text());
+        let pre_process = self.combine_text();
         self.raw_text = Some(pre_process);
         if self.is_contain_redirection() {
             self.is_redirect = true;
@@ -174,14 +175,14 @@ impl EchoCommand {
             let full_path = parts.join("/");
             
 
-            match CdCommand::new(&self.current_dir, &vec![full_path]) {
+            match CdCommand::new(&self.current_dir, &[full_path]) {
                 Ok(cd) => match cd.run() {
                     Ok(res) => {
                         res
                     }
                     Err(err) => return Err(err),
                 },
-                Err((err, _)) => return Err(err),
+                Err(err) => return Err(err),
             }
         } else {
             self.current_dir.clone()
@@ -197,6 +198,11 @@ impl EchoCommand {
             Some(EchoArg::AppendInto(_)) => true,
             _ => false,
         };
+        // `set -C` refuses to truncate an existing file unless `>|` is used
+        let is_forced = self.redir_option == Some(EchoArg::WriteInto(">|".to_string()));
+        if !is_append && !is_forced && SHELL_OPTIONS.lock().unwrap().noclobber && self.output_dir.exists() {
+            return Err(format!("{}: cannot overwrite existing file", self.output_dir.display()).into());
+        }
         let mut file_option = match fs::OpenOptions::new()
             .write(true)
             .append(is_append)
@@ -282,3 +288,32 @@ impl EchoCommand {
         }
     }
 }
+
+pub struct EchoBuiltin;
+
+impl Builtin for EchoBuiltin {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn help(&self) -> &'static str {
+        "echo [-ne] [text ...] [> file]\nPrint text, or write it to a file with > or >>."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[
+            OptionSpec { flag: "-n", help: "don't print the trailing newline" },
+            OptionSpec { flag: "-e", help: "interpret backslash escapes" },
+        ]
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        match EchoCommand::new(args.to_vec(), &ctx.current_dir).run() {
+            Ok(_) => Ok(0),
+            Err(err) => {
+                print_error!("echo: {}", err.to_string());
+                Ok(1)
+            }
+        }
+    }
+}
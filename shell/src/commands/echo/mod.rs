@@ -2,4 +2,4 @@ mod echo;
 mod arg_type;
 mod table_fmt;
 
-pub use echo::EchoCommand;
\ No newline at end of file
+pub use echo::EchoBuiltin;
\ No newline at end of file
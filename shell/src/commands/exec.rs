@@ -0,0 +1,244 @@
+use std::collections::BTreeSet;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+use regex::Regex;
+use crate::commands::builtin::{Builtin, Context};
+use crate::commands::executable_cmds::{ExecOutcome, ExecutableCmds};
+use crate::commands::CmdParser;
+use crate::interpreter::ErrExit;
+use crate::utils::{JobControl, WordSplitter};
+use crate::{print_error, SHELL_OPTIONS};
+
+/// The descriptors `reserve_fds` filled
+static RESERVED: Mutex<BTreeSet<i32>> = Mutex::new(BTreeSet::new());
+/// The reserved descriptors `exec` has not opened since. They only hold
+/// /dev/null, so using them is an error.
+static PLACEHOLDERS: Mutex<BTreeSet<i32>> = Mutex::new(BTreeSet::new());
+
+/// One `[n]op target` word, e.g. `3>log`, `>>out`, `<in` or `2>&1`
+enum Redirection {
+    Write { fd: i32, path: String, force: bool },
+    Append { fd: i32, path: String },
+    Read { fd: i32, path: String },
+    Dup { fd: i32, target: i32 },
+    Close { fd: i32 },
+}
+
+impl Redirection {
+    /// Parses the redirections at the front of `args` and returns them with
+    /// the number of words they took. The operator and its target may be
+    /// one word or two.
+    fn parse(args: &[String]) -> Result<(Vec<Self>, usize), Box<dyn std::error::Error>> {
+        let regex = Regex::new(r"^(\d*)(>>|>\||>&|<&|>|<)(.*)$").unwrap();
+        let mut redirections = vec![];
+        let mut rest = args;
+        while let Some(caps) = rest.first().and_then(|word| regex.captures(word)) {
+            let op = caps[2].to_string();
+            let fd = match &caps[1] {
+                "" if op.starts_with('<') => 0,
+                "" => 1,
+                fd => fd.parse()?,
+            };
+            let (target, used) = match &caps[3] {
+                "" => match rest.get(1) {
+                    Some(target) => (target.unquote(), 2),
+                    None => return Err("exec: syntax error near unexpected end of line".into()),
+                },
+                target => (target.to_string().unquote(), 1),
+            };
+            rest = &rest[used..];
+            let redirection = match op.as_str() {
+                ">&" | "<&" if target == "-" => Redirection::Close { fd },
+                ">&" | "<&" => match target.parse() {
+                    Ok(target) => Redirection::Dup { fd, target },
+                    Err(_) => return Err(format!("exec: {}: ambiguous redirect", target).into()),
+                },
+                ">>" => Redirection::Append { fd, path: target },
+                "<" => Redirection::Read { fd, path: target },
+                _ => Redirection::Write { fd, path: target, force: op == ">|" },
+            };
+            redirections.push(redirection);
+        }
+        Ok((redirections, args.len() - rest.len()))
+    }
+
+    /// Points the shell's own `fd` at the target
+    #[cfg(unix)]
+    fn apply(&self, current_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::fd::IntoRawFd;
+        let (fd, file) = match self {
+            Redirection::Write { fd, path, force } => {
+                let path = current_dir.join(path);
+                if !force && SHELL_OPTIONS.lock().unwrap().noclobber && path.is_file() {
+                    return Err(format!("exec: {}: cannot overwrite existing file", path.display()).into());
+                }
+                (*fd, OpenOptions::new().write(true).create(true).truncate(true).open(&path))
+            }
+            Redirection::Append { fd, path } => {
+                (*fd, OpenOptions::new().append(true).create(true).open(current_dir.join(path)))
+            }
+            Redirection::Read { fd, path } => (*fd, OpenOptions::new().read(true).open(current_dir.join(path))),
+            Redirection::Dup { fd, target } => {
+                let mut placeholders = PLACEHOLDERS.lock().unwrap();
+                // SAFETY: dup2 only works on descriptor numbers
+                if placeholders.contains(target) || unsafe { libc::dup2(*target, *fd) } == -1 {
+                    return Err(format!("exec: {}: bad file descriptor", target).into());
+                }
+                placeholders.remove(fd);
+                return Ok(());
+            }
+            Redirection::Close { fd } => {
+                ExecCommand::close(*fd);
+                return Ok(());
+            }
+        };
+        let file = file.map_err(|err| format!("exec: {}", err))?.into_raw_fd();
+        // SAFETY: `file` was just opened and is owned here; dup2 also clears
+        // the close-on-exec flag std sets on it
+        unsafe {
+            libc::dup2(file, fd);
+            if file != fd {
+                libc::close(file);
+            }
+        }
+        PLACEHOLDERS.lock().unwrap().remove(&fd);
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn apply(&self, _current_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Err("exec: redirections are not supported on this platform".into())
+    }
+}
+
+pub struct ExecCommand;
+
+impl Builtin for ExecCommand {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn help(&self) -> &'static str {
+        "exec [redirection ...] [command [args ...]]\nReplace the shell with command, or apply the redirections to the shell itself."
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        match Self::exec(args, &ctx.current_dir) {
+            Ok(ExecOutcome::Status(status)) => Ok(status),
+            // there is no process to replace with a script; running it and
+            // ending the shell with its status is the same
+            Ok(ExecOutcome::Script(path)) => Err(ErrExit(CmdParser::run_script(path)).into()),
+            Err(err) => {
+                print_error!("{}", err.to_string());
+                Ok(1)
+            }
+        }
+    }
+}
+
+impl ExecCommand {
+    /// Descriptors 3 to 9 are left for `exec n>file`. Filling the free ones
+    /// with close-on-exec placeholders before anything else opens files keeps
+    /// the shell's own descriptors, like the terminal event reader's, above
+    /// them.
+    #[cfg(unix)]
+    pub fn reserve_fds() {
+        let mut reserved = RESERVED.lock().unwrap();
+        for _ in 3..=9 {
+            let fd = Self::open_placeholder();
+            if fd == -1 {
+                break;
+            }
+            if fd > 9 {
+                // SAFETY: `fd` was just opened and nothing else has it
+                unsafe { libc::close(fd) };
+                break;
+            }
+            reserved.insert(fd);
+        }
+        *PLACEHOLDERS.lock().unwrap() = reserved.clone();
+    }
+
+    #[cfg(not(unix))]
+    pub fn reserve_fds() {}
+
+    #[cfg(unix)]
+    fn open_placeholder() -> i32 {
+        // SAFETY: the path is a valid C string
+        unsafe { libc::open(c"/dev/null".as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC) }
+    }
+
+    /// `exec n>&-`. A reserved descriptor gets its placeholder back rather
+    /// than being freed, so the shell's own files never land on it.
+    #[cfg(unix)]
+    fn close(fd: i32) {
+        let mut placeholders = PLACEHOLDERS.lock().unwrap();
+        if placeholders.contains(&fd) {
+            return;
+        }
+        // SAFETY: closing or replacing a descriptor the user opened with
+        // exec, which nothing else in the shell owns
+        unsafe {
+            if RESERVED.lock().unwrap().contains(&fd) {
+                let placeholder = Self::open_placeholder();
+                if placeholder != -1 {
+                    libc::dup2(placeholder, fd);
+                    libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                    libc::close(placeholder);
+                    placeholders.insert(fd);
+                    return;
+                }
+            }
+            libc::close(fd);
+        }
+    }
+
+    /// `exec [redirection...] [cmd [args...]]`. With a command, the shell is
+    /// replaced by it and this only returns when it could not be started.
+    /// Without one, the redirections stay in place for the rest of the
+    /// session.
+    fn exec(args: &[String], current_dir: &Path) -> Result<ExecOutcome, Box<dyn std::error::Error>> {
+        let (redirections, used) = Redirection::parse(args)?;
+        let command = &args[used..];
+        for redirection in &redirections {
+            redirection.apply(current_dir)?;
+        }
+        let (cmd, args) = match command.split_first() {
+            Some((cmd, args)) => (cmd.unquote(), args.iter().map(|arg| arg.unquote()).collect::<Vec<_>>()),
+            None => return Ok(ExecOutcome::Status(0)),
+        };
+        let path = match ExecutableCmds::resolve(&cmd, current_dir) {
+            Ok(path) => path,
+            Err(err) => {
+                print_error!("cotsh: exec: {}: {}", cmd, err);
+                return Ok(ExecOutcome::Status(err.status()));
+            }
+        };
+        if ExecutableCmds::is_shell_script(&path) {
+            return Ok(ExecOutcome::Script(path));
+        }
+        let mut command = Command::new(&path);
+        command.args(&args).current_dir(current_dir);
+        let err = Self::replace(command, &cmd);
+        print_error!("cotsh: exec: {}: {}", cmd, err);
+        Ok(ExecOutcome::Status(126))
+    }
+
+    #[cfg(unix)]
+    fn replace(mut command: Command, name: &str) -> std::io::Error {
+        use std::io::Write;
+        use std::os::unix::process::CommandExt;
+        let _ = std::io::stdout().flush();
+        command.arg0(name);
+        JobControl::prepare_exec(&mut command);
+        command.exec()
+    }
+
+    #[cfg(not(unix))]
+    fn replace(_command: Command, _name: &str) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::Unsupported, "not supported on this platform")
+    }
+}
+
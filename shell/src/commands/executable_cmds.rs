@@ -1,11 +1,51 @@
-use crate::DEBUG_MODE;
+use crate::{COMMAND_HASH, DEBUG_MODE};
 use crate::DebugPrint;
 use crate::dlog;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use crate::utils::JobControl;
 use std::{env, fs};
 use getset::{Getters, Setters};
 
+/// Why a command word could not be turned into something runnable
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecError {
+    NotFound,
+    PermissionDenied,
+    IsADirectory,
+}
+
+impl ExecError {
+    /// 127 when nothing was found, 126 when it was found but can't run
+    pub fn status(&self) -> i32 {
+        match self {
+            ExecError::NotFound => 127,
+            ExecError::PermissionDenied | ExecError::IsADirectory => 126,
+        }
+    }
+}
+
+impl std::fmt::Display for ExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecError::NotFound => write!(f, "command not found"),
+            ExecError::PermissionDenied => write!(f, "permission denied"),
+            ExecError::IsADirectory => write!(f, "is a directory"),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+/// What running an external command produced
+pub enum ExecOutcome {
+    Status(i32),
+    /// The file is executable but has no `#!` line and no binary header;
+    /// it is meant to be run as a cotsh script
+    Script(PathBuf),
+}
+
 #[derive(Debug, Getters, Setters)]
 pub struct ExecutableCmds {
     #[getset(get = "pub")]
@@ -20,51 +60,176 @@ pub struct ExecutableCmds {
 
 impl ExecutableCmds {
     pub fn new(cmd: &str, args: &Vec<String>, current_path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        match Self::find_executable(&cmd) {
-            Some(path) => {
-                dlog!("found executable: {}", path);
-                match Self::is_executable(&Path::new(&path)) {
-                    true => Ok(Self {
-                        executable_path: path,
-                        executable: cmd.into(),
-                        args: args.clone(),
-                        current_path: current_path.clone()
-                    }),
-
-                    false => Err("command not found".into()),
-                }
-            }
-            None => Err("command not found".into()),
+        let path = Self::resolve(cmd, current_path)?;
+        dlog!("found executable: {}", path.display());
+        Ok(Self {
+            executable_path: path.to_string_lossy().into_owned(),
+            executable: cmd.into(),
+            args: args.clone(),
+            current_path: current_path.clone()
+        })
+    }
+
+    /// Looks `cmd` up in `path_var` instead of `$PATH`, leaving the hash
+    /// table alone; `command -p` uses it with a default search path
+    pub fn new_in(cmd: &str, path_var: &str) -> Result<Self, ExecError> {
+        let path = match cmd.contains('/') {
+            true => Self::resolve(cmd, Path::new(""))?,
+            false => Self::find_in(cmd, path_var).ok_or(ExecError::NotFound)?,
+        };
+        Ok(Self {
+            executable_path: path.to_string_lossy().into_owned(),
+            executable: cmd.into(),
+            args: vec![],
+            current_path: PathBuf::new(),
+        })
+    }
+
+    /// The same command with the arguments and directory it runs with,
+    /// reusing the path found when the command word was parsed
+    pub fn with_args(&self, args: Vec<String>, current_path: &Path) -> Self {
+        Self {
+            executable_path: self.executable_path.clone(),
+            executable: self.executable.clone(),
+            args,
+            current_path: current_path.to_path_buf(),
         }
     }
 
-    pub fn is_executable(path: &Path) -> bool {
-        fs::metadata(path)
-            .map(|metadata| metadata.is_file())
-            .unwrap_or(false)
+    /// A word containing `/` names a file directly, relative to
+    /// `current_path`; anything else is looked up in the hash table, then
+    /// in `PATH`
+    pub fn resolve(cmd: &str, current_path: &Path) -> Result<PathBuf, ExecError> {
+        if cmd.contains('/') {
+            let path = current_path.join(cmd);
+            return match Self::check(&path) {
+                Ok(()) => Ok(path),
+                Err(err) => Err(err),
+            };
+        }
+        if let Some(path) = COMMAND_HASH.lock().unwrap().lookup(cmd) {
+            return Ok(path);
+        }
+        let path_var = env::var("PATH").unwrap_or_default();
+        let path = Self::find_in(cmd, &path_var).ok_or(ExecError::NotFound)?;
+        COMMAND_HASH.lock().unwrap().insert(cmd, path.clone(), 1);
+        Ok(path)
     }
 
-    fn find_executable(cmd: &str) -> Option<String> {
-        if let Ok(path) = env::var("PATH") {
-            for dir in path.split(":") {
-                let path = Path::new(dir).join(&cmd);
-                if Self::is_executable(&path) {
-                    return Some(path.to_string_lossy().into_owned());
-                }
-            }
+    /// Whether `resolve` would find `cmd`, without adding it to the hash
+    /// table or counting a hit; the highlighter asks this for every key
+    pub fn exists(cmd: &str, current_path: &Path) -> bool {
+        if cmd.contains('/') {
+            return Self::check(&current_path.join(cmd)).is_ok();
         }
-        None
+        if COMMAND_HASH.lock().unwrap().peek(cmd).is_some() {
+            return true;
+        }
+        Self::find_in(cmd, &env::var("PATH").unwrap_or_default()).is_some()
+    }
+
+    fn check(path: &Path) -> Result<(), ExecError> {
+        let metadata = fs::metadata(path).map_err(|_| ExecError::NotFound)?;
+        if metadata.is_dir() {
+            return Err(ExecError::IsADirectory);
+        }
+        match Self::is_executable(path) {
+            true => Ok(()),
+            false => Err(ExecError::PermissionDenied),
+        }
+    }
+
+    /// A regular file with at least one execute bit set
+    pub fn is_executable(path: &Path) -> bool {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return false,
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.permissions().mode() & 0o111 != 0
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = metadata;
+            true
+        }
+    }
+
+    /// The first executable `cmd` in the `:` separated `path_var`
+    fn find_in(cmd: &str, path_var: &str) -> Option<PathBuf> {
+        Self::search(cmd, path_var).next()
+    }
+
+    /// Every executable `cmd` in `$PATH`, in search order
+    pub fn find_all(cmd: &str) -> Vec<PathBuf> {
+        let path_var = env::var("PATH").unwrap_or_default();
+        Self::search(cmd, &path_var).collect()
     }
 
-    pub fn execute_cmd(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let _child = Command::new(&self.executable)
+    fn search<'a>(cmd: &'a str, path_var: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+        path_var
+            .split(":")
+            .filter(move |_| !path_var.is_empty())
+            // an empty entry means the current directory
+            .map(move |dir| Path::new(if dir.is_empty() { "." } else { dir }).join(cmd))
+            .filter(|path| Self::is_executable(path))
+    }
+
+    /// Files the kernel would reject with ENOEXEC: no `#!` and no known
+    /// binary header. libc's exec family quietly hands those to /bin/sh, so
+    /// they are caught here instead.
+    pub fn is_shell_script(path: &Path) -> bool {
+        const MAGICS: [&[u8]; 6] = [
+            b"#!",
+            b"\x7fELF",
+            b"\xfe\xed\xfa\xce",
+            b"\xfe\xed\xfa\xcf",
+            b"\xce\xfa\xed\xfe",
+            b"\xcf\xfa\xed\xfe",
+        ];
+        let mut header = [0u8; 4];
+        let len = match fs::File::open(path).and_then(|mut file| file.read(&mut header)) {
+            Ok(len) => len,
+            Err(_) => return false,
+        };
+        !MAGICS.iter().any(|magic| header[..len].starts_with(magic))
+    }
+
+    /// Runs the command in the foreground and waits for it, returning its
+    /// exit code, or 128 + the signal number when a signal ended it
+    pub fn execute_cmd(&self) -> Result<ExecOutcome, Box<dyn std::error::Error>> {
+        let path = PathBuf::from(&self.executable_path);
+        if Self::is_shell_script(&path) {
+            return Ok(ExecOutcome::Script(path));
+        }
+        let mut command = Command::new(&path);
+        command
             .current_dir(&self.current_path)
             .args(&self.args)
             .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()?
-            .wait()?;
+            .stderr(Stdio::inherit());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.arg0(&self.executable);
+        }
+        JobControl::prepare(&mut command);
 
-        Ok(())
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) if err.raw_os_error() == Some(ENOEXEC) => return Ok(ExecOutcome::Script(path)),
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Err(ExecError::PermissionDenied.into())
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Ok(ExecOutcome::Status(JobControl::wait(child)?))
     }
 }
+
+#[cfg(unix)]
+const ENOEXEC: i32 = libc::ENOEXEC;
+#[cfg(not(unix))]
+const ENOEXEC: i32 = 8;
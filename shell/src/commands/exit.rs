@@ -1,19 +1,48 @@
-use crate::print_success;
+use std::sync::atomic::Ordering;
+use crate::commands::builtin::{Builtin, Context};
+use crate::interpreter::ErrExit;
+use crate::{print_error, print_success, LAST_STATUS};
 
 pub struct ExitCommand;
 
 impl ExitCommand{
 
-    pub fn exit(code: Option<&String>) -> Result<(), Box<dyn std::error::Error>> {
-        if code.is_some(){
-            let regex = regex::Regex::new(r"^\d+$").unwrap();
-            match regex.is_match(&code.as_ref().unwrap()) {
-                true => {},
-                false => return Err("Invalid exit code".into())
+    /// The code to exit with: the given one, or `$?` when none is given.
+    /// Exiting is left to the caller so EXIT traps can run first.
+    pub fn exit(code: Option<&String>) -> Result<i32, Box<dyn std::error::Error>> {
+        let code = match code {
+            Some(code) => {
+                let regex = regex::Regex::new(r"^\d+$").unwrap();
+                match regex.is_match(code) {
+                    true => {},
+                    false => return Err(format!("exit: {}: numeric argument required", code).into())
+                }
+                code.parse::<i32>()?
             }
-        }
-        let code = code.as_ref().unwrap().parse::<i32>()?;
+            None => LAST_STATUS.load(Ordering::SeqCst),
+        };
         print_success!("Exiting with code {}", code);
-        std::process::exit(code);
+        Ok(code)
+    }
+}
+
+impl Builtin for ExitCommand {
+    fn name(&self) -> &'static str {
+        "exit"
+    }
+
+    fn help(&self) -> &'static str {
+        "exit [n]\nExit the shell with status n, or with the status of the last command."
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        match Self::exit(args.first()) {
+            // the shell or script running this command decides how to end
+            Ok(code) => Err(ErrExit(code).into()),
+            Err(err) => {
+                print_error!("{}", err.to_string());
+                Ok(2)
+            }
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use crate::commands::builtin::{status, Builtin, Context, OptionSpec};
+use crate::commands::executable_cmds::ExecutableCmds;
+use crate::utils::WordSplitter;
+use crate::{print_success, COMMAND_HASH, PLUGINS};
+
+struct HashEntry {
+    path: PathBuf,
+    hits: u32,
+}
+
+/// Remembers where `PATH` lookups found each command name. The table is
+/// tied to the `PATH` it was filled from and is emptied when that changes.
+#[derive(Default)]
+pub struct CommandHash {
+    path_var: String,
+    entries: BTreeMap<String, HashEntry>,
+    /// Every executable in `PATH`, as completion last listed them
+    commands: Option<BTreeSet<String>>,
+}
+
+impl CommandHash {
+    fn sync_path(&mut self) {
+        let path_var = env::var("PATH").unwrap_or_default();
+        if path_var != self.path_var {
+            self.clear();
+            self.path_var = path_var;
+        }
+    }
+
+    /// The names of the executables in `PATH`. The directories are listed
+    /// once per `PATH`, and again when `refresh` is set.
+    pub fn path_commands(&mut self, refresh: bool) -> &BTreeSet<String> {
+        self.sync_path();
+        if refresh {
+            self.commands = None;
+        }
+        let path_var = &self.path_var;
+        self.commands.get_or_insert_with(|| {
+            let mut names = BTreeSet::new();
+            for dir in env::split_paths(path_var) {
+                let entries = match std::fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(_) => continue,
+                };
+                for entry in entries.filter_map(|entry| entry.ok()) {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if !names.contains(&name) && ExecutableCmds::is_executable(&entry.path()) {
+                        names.insert(name);
+                    }
+                }
+            }
+            names
+        })
+    }
+
+    /// The cached path for `name`. An entry whose file is gone or no longer
+    /// executable is dropped so the caller searches `PATH` again.
+    pub fn lookup(&mut self, name: &str) -> Option<PathBuf> {
+        self.sync_path();
+        let entry = self.entries.get_mut(name)?;
+        if !ExecutableCmds::is_executable(&entry.path) {
+            self.entries.remove(name);
+            return None;
+        }
+        entry.hits += 1;
+        Some(entry.path.clone())
+    }
+
+    /// The cached path for `name`, leaving the hit count and a stale table
+    /// as they are
+    pub fn peek(&self, name: &str) -> Option<&Path> {
+        if self.path_var != env::var("PATH").unwrap_or_default() {
+            return None;
+        }
+        let entry = self.entries.get(name)?;
+        ExecutableCmds::is_executable(&entry.path).then_some(entry.path.as_path())
+    }
+
+    pub fn insert(&mut self, name: &str, path: PathBuf, hits: u32) {
+        self.sync_path();
+        self.entries.insert(name.to_string(), HashEntry { path, hits });
+    }
+
+    fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.commands = None;
+    }
+}
+
+pub struct HashCommand;
+
+impl Builtin for HashCommand {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn help(&self) -> &'static str {
+        "hash [-r] [-p path name] [-dt name ...] [name ...]\nRemember or show where commands were found in PATH."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[
+            OptionSpec { flag: "-r", help: "forget every remembered location and read the plugins again" },
+            OptionSpec { flag: "-p", help: "use path as the location of name" },
+            OptionSpec { flag: "-d", help: "forget the location of each name" },
+            OptionSpec { flag: "-t", help: "print the remembered location of each name" },
+        ]
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        Ok(status(Self::hash(args, &ctx.current_dir), 1))
+    }
+}
+
+impl HashCommand {
+    /// `hash [-r] [-p path name] [-dt name...] [name...]`
+    fn hash(args: &[String], current_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.unquote()).collect();
+        let mut table = COMMAND_HASH.lock().unwrap();
+        table.sync_path();
+        match args.first().map(String::as_str) {
+            None => {
+                if table.entries.is_empty() {
+                    print_success!("hash: hash table empty");
+                    return Ok(());
+                }
+                print_success!("hits\tcommand");
+                for entry in table.entries.values() {
+                    print_success!("{:>4}\t{}", entry.hits, entry.path.display());
+                }
+                Ok(())
+            }
+            Some("-r") => {
+                table.clear();
+                PLUGINS.lock().unwrap().clear();
+                Ok(())
+            }
+            Some("-p") => match (args.get(1), args.get(2)) {
+                (Some(path), Some(name)) => {
+                    let path = current_dir.join(path);
+                    table.insert(name, path, 0);
+                    Ok(())
+                }
+                _ => Err("hash: usage: hash -p path name".into()),
+            },
+            Some("-d") => Self::for_each(&args[1..], |name| match table.remove(name) {
+                true => Ok(()),
+                false => Err(format!("hash: {}: not found", name)),
+            }),
+            Some("-t") => Self::for_each(&args[1..], |name| match table.entries.get(name) {
+                Some(entry) => {
+                    print_success!("{}", entry.path.display());
+                    Ok(())
+                }
+                None => Err(format!("hash: {}: not found", name)),
+            }),
+            Some(_) => {
+                // the lookup itself fills the table; the lock has to be free
+                drop(table);
+                Self::for_each(&args, |name| match ExecutableCmds::resolve(name, current_dir) {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(format!("hash: {}: not found", name)),
+                })
+            }
+        }
+    }
+
+    /// Applies `f` to every name and fails if any of them failed
+    fn for_each<F>(names: &[String], mut f: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnMut(&str) -> Result<(), String>,
+    {
+        let mut res = Ok(());
+        for name in names {
+            if let Err(err) = f(name) {
+                res = Err(err.into());
+            }
+        }
+        res
+    }
+}
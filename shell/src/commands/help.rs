@@ -0,0 +1,52 @@
+use crate::commands::builtin::{Builtin, Context};
+use crate::commands::plugin::Plugin;
+use crate::utils::WordSplitter;
+use crate::{print_error, print_success, BUILTINS};
+
+pub struct HelpCommand;
+
+impl Builtin for HelpCommand {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn help(&self) -> &'static str {
+        "help [name ...]\nList the builtins and plugins, or describe the given builtins and their options."
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        if args.is_empty() {
+            let builtins: Vec<&dyn Builtin> = BUILTINS.iter().collect();
+            let width = builtins.iter().map(|builtin| builtin.name().len()).max().unwrap_or(0);
+            for builtin in builtins {
+                let synopsis = builtin.help().lines().nth(1).unwrap_or_default();
+                print_success!("{:width$}  {}", builtin.name(), synopsis, width = width);
+            }
+            let plugins = Plugin::all();
+            if !plugins.is_empty() {
+                print_success!("\nplugins:");
+                for plugin in plugins {
+                    print_success!("{:width$}  {}", plugin.name(), plugin.path().display(), width = width);
+                }
+            }
+            return Ok(0);
+        }
+        let mut status = 0;
+        for name in args {
+            let name = name.unquote();
+            let builtin = match BUILTINS.get(&name) {
+                Some(builtin) => builtin,
+                None => {
+                    print_error!("help: no help topics match `{}'", name);
+                    status = 1;
+                    continue;
+                }
+            };
+            print_success!("{}", builtin.help());
+            for option in builtin.options() {
+                print_success!("    {:4}{}", option.flag, option.help);
+            }
+        }
+        Ok(status)
+    }
+}
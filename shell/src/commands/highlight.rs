@@ -0,0 +1,171 @@
+use crossterm::style::{Attribute, Color, ContentStyle};
+use crate::commands::builtin::{Builtin, Context, OptionSpec};
+use crate::utils::WordSplitter;
+use crate::{print_error, print_success, THEME};
+
+/// A part of the command line the theme gives a style
+#[derive(Clone, Copy, PartialEq)]
+pub enum Role {
+    /// A command word that resolves
+    Command,
+    /// A command word that doesn't
+    Error,
+    String,
+    Variable,
+    Operator,
+    Redirect,
+    Comment,
+    /// A word naming a file or directory that exists
+    Path,
+    /// A bracket or quote at the cursor and its partner
+    Match,
+    /// What history suggests after the cursor
+    Suggestion,
+}
+
+/// Every role, by the name `highlight` knows it by, with its default style
+const ROLES: [(Role, &str, &str); 10] = [
+    (Role::Command, "command", "green"),
+    (Role::Error, "error", "red"),
+    (Role::String, "string", "yellow"),
+    (Role::Variable, "variable", "cyan"),
+    (Role::Operator, "operator", "magenta"),
+    (Role::Redirect, "redirect", "blue"),
+    (Role::Comment, "comment", "dark_grey"),
+    (Role::Path, "path", "underline"),
+    (Role::Match, "match", "reverse"),
+    (Role::Suggestion, "suggestion", "dim"),
+];
+
+/// The style of each role, as the words `highlight` was given
+pub struct Theme {
+    styles: Vec<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self { styles: ROLES.iter().map(|(_, _, style)| style.to_string()).collect() }
+    }
+}
+
+impl Theme {
+    fn index(role: Role) -> usize {
+        ROLES.iter().position(|(known, _, _)| *known == role).unwrap_or(0)
+    }
+
+    pub fn style(&self, role: Role) -> ContentStyle {
+        Self::parse(&self.styles[Self::index(role)]).unwrap_or_default()
+    }
+
+    /// Turns words like `bold green on_dark_grey` into a style: colour names,
+    /// `#rrggbb` or a number from the 256-colour palette for the text, the
+    /// same with `on_` in front for the background, and `bold`, `dim`,
+    /// `italic`, `underline` or `reverse`. `none` is no style at all.
+    pub(crate) fn parse(words: &str) -> Result<ContentStyle, String> {
+        let mut style = ContentStyle::new();
+        for word in words.split_whitespace() {
+            let attribute = match word {
+                "none" => continue,
+                "bold" => Some(Attribute::Bold),
+                "dim" => Some(Attribute::Dim),
+                "italic" => Some(Attribute::Italic),
+                "underline" => Some(Attribute::Underlined),
+                "reverse" => Some(Attribute::Reverse),
+                _ => None,
+            };
+            if let Some(attribute) = attribute {
+                style.attributes.set(attribute);
+                continue;
+            }
+            match word.strip_prefix("on_") {
+                Some(color) => style.background_color = Some(Self::color(color).ok_or(word)?),
+                None => style.foreground_color = Some(Self::color(word).ok_or(word)?),
+            }
+        }
+        Ok(style)
+    }
+
+    fn color(word: &str) -> Option<Color> {
+        if let Some(hex) = word.strip_prefix('#').filter(|hex| hex.len() == 6) {
+            let channel = |at: usize| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok();
+            return Some(Color::Rgb { r: channel(0)?, g: channel(2)?, b: channel(4)? });
+        }
+        match word.parse::<u8>() {
+            Ok(value) => Some(Color::AnsiValue(value)),
+            Err(_) => Color::try_from(word).ok(),
+        }
+    }
+}
+
+pub struct HighlightCommand;
+
+impl Builtin for HighlightCommand {
+    fn name(&self) -> &'static str {
+        "highlight"
+    }
+
+    fn help(&self) -> &'static str {
+        "highlight [-pr] [role [style ...]]\nSet how each part of the command line is coloured as it is typed, or print the theme.\nRoles: command error string variable operator redirect comment path match suggestion."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[
+            OptionSpec { flag: "-p", help: "print the styles" },
+            OptionSpec { flag: "-r", help: "put the styles back to their defaults" },
+        ]
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.unquote()).collect();
+        let (mut print, mut reset) = (false, false);
+        let mut rest = args.as_slice();
+        while let Some(flags) = rest.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+            for flag in flags.chars().skip(1) {
+                match flag {
+                    'p' => print = true,
+                    'r' => reset = true,
+                    _ => {
+                        print_error!("highlight: -{}: invalid option", flag);
+                        return Ok(2);
+                    }
+                }
+            }
+            rest = &rest[1..];
+        }
+
+        let mut theme = THEME.lock().unwrap();
+        let role = match rest.first() {
+            Some(name) => match ROLES.iter().position(|(_, known, _)| known == name) {
+                Some(index) => Some(index),
+                None => {
+                    print_error!("highlight: {}: unknown role", name);
+                    return Ok(1);
+                }
+            },
+            None => None,
+        };
+        let roles = match role {
+            Some(index) => index..index + 1,
+            None => 0..ROLES.len(),
+        };
+        if reset {
+            for index in roles {
+                theme.styles[index] = ROLES[index].2.to_string();
+            }
+            return Ok(0);
+        }
+        if print || rest.len() < 2 {
+            for index in roles {
+                print_success!("highlight {} {}", ROLES[index].1, theme.styles[index]);
+            }
+            return Ok(0);
+        }
+        let words = rest[1..].join(" ");
+        if let Err(word) = Theme::parse(&words) {
+            print_error!("highlight: {}: invalid style", word);
+            return Ok(1);
+        }
+        theme.styles[roles.start] = words;
+        Ok(0)
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use crossterm::style::ContentStyle;
+use crate::commands::executable_cmds::ExecutableCmds;
+use crate::commands::highlight::Role;
+use crate::commands::plugin::Plugin;
+use crate::{ALIASES, BUILTINS, CURRENT_DIR, THEME};
+
+/// The word being scanned
+#[derive(Default)]
+struct Word {
+    start: usize,
+    /// Its text without quotes and escapes
+    text: String,
+    /// Whether part of it is a variable, so it can't be checked
+    expands: bool,
+}
+
+/// Roles found in one line: one per byte, where words name paths, and
+/// which brackets and quotes pair up
+struct Scan {
+    roles: Vec<Option<Role>>,
+    paths: Vec<(usize, usize)>,
+    pairs: Vec<(usize, usize)>,
+}
+
+impl Scan {
+    fn mark(&mut self, start: usize, end: usize, role: Role) {
+        self.roles[start..end].iter_mut().for_each(|slot| *slot = Some(role));
+    }
+
+    /// Marks what has no role yet, leaving strings and variables in a word
+    /// their own colour
+    fn fill(&mut self, start: usize, end: usize, role: Role) {
+        self.roles[start..end].iter_mut().filter(|slot| slot.is_none()).for_each(|slot| *slot = Some(role));
+    }
+}
+
+/// Colours the command line as it is typed, using the theme the
+/// `highlight` builtin sets
+#[derive(Default)]
+pub struct Highlighter {
+    /// Whether each command word looked up so far resolves
+    commands: HashMap<String, bool>,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `line` cut into pieces with their styles, with the bracket or quote
+    /// under or just before `cursor` shown with its partner, and then the
+    /// text `suggestion` offers after it
+    pub fn spans<'a>(&mut self, line: &'a str, cursor: usize, suggestion: &'a str) -> Vec<(&'a str, ContentStyle)> {
+        let scan = self.scan(line);
+        let matched = [cursor, line[..cursor].char_indices().next_back().map_or(usize::MAX, |(index, _)| index)]
+            .iter()
+            .find_map(|at| scan.pairs.iter().find(|(open, close)| open == at || close == at).copied());
+
+        let theme = THEME.lock().unwrap();
+        let (path, highlight) = (theme.style(Role::Path), theme.style(Role::Match));
+        let mut spans: Vec<(&str, ContentStyle)> = Vec::new();
+        let mut start = 0;
+        let mut current = None;
+        for (index, _) in line.char_indices() {
+            let mut style = scan.roles[index].map(|role| theme.style(role)).unwrap_or_default();
+            if scan.paths.iter().any(|(from, to)| (*from..*to).contains(&index)) {
+                style = Self::overlay(style, path);
+            }
+            if matched.is_some_and(|(open, close)| index == open || index == close) {
+                style = Self::overlay(style, highlight);
+            }
+            if current.is_some_and(|current| current != style) {
+                spans.push((&line[start..index], current.unwrap()));
+                start = index;
+            }
+            current = Some(style);
+        }
+        if let Some(style) = current {
+            spans.push((&line[start..], style));
+        }
+        if !suggestion.is_empty() {
+            spans.push((suggestion, theme.style(Role::Suggestion)));
+        }
+        spans
+    }
+
+    /// `top` drawn over `base`: its colours where it has them, and the
+    /// attributes of both
+    fn overlay(mut base: ContentStyle, top: ContentStyle) -> ContentStyle {
+        base.foreground_color = top.foreground_color.or(base.foreground_color);
+        base.background_color = top.background_color.or(base.background_color);
+        base.attributes = base.attributes | top.attributes;
+        base
+    }
+
+    fn scan(&mut self, line: &str) -> Scan {
+        let mut scan = Scan { roles: vec![None; line.len()], paths: Vec::new(), pairs: Vec::new() };
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let end_of = |index: usize| chars.get(index).map_or(line.len(), |(at, _)| *at);
+        // open brackets, and the quote the scan is inside of
+        let mut brackets: Vec<(char, usize)> = Vec::new();
+        let mut quote: Option<(char, usize)> = None;
+        let mut word: Option<Word> = None;
+        let mut command = true;
+        // the word after a redirection is a file
+        let mut target = false;
+
+        let mut index = 0;
+        while index < chars.len() {
+            let (at, char) = chars[index];
+            let next = chars.get(index + 1).map(|(_, char)| *char);
+            index += 1;
+            if let Some((open, opened_at)) = quote {
+                let current = word.get_or_insert_with(|| Word { start: opened_at, ..Word::default() });
+                match char {
+                    _ if char == open => {
+                        scan.mark(at, at + 1, Role::String);
+                        scan.pairs.push((opened_at, at));
+                        quote = None;
+                    }
+                    '$' if open == '"' => {
+                        index = Self::variable(&chars, index, &mut scan);
+                        current.expands = true;
+                    }
+                    '\\' if open == '"' && next.is_some() => {
+                        scan.mark(at, end_of(index + 1), Role::String);
+                        current.text.push(next.unwrap_or_default());
+                        index += 1;
+                    }
+                    _ => {
+                        scan.mark(at, at + char.len_utf8(), Role::String);
+                        current.text.push(char);
+                    }
+                }
+                continue;
+            }
+
+            match char {
+                ' ' | '\t' => self.end_word(word.take(), at, &mut command, &mut target, &mut scan),
+                '#' if word.is_none() => {
+                    scan.mark(at, line.len(), Role::Comment);
+                    break;
+                }
+                '\\' => {
+                    let current = word.get_or_insert_with(|| Word { start: at, ..Word::default() });
+                    if let Some(next) = next {
+                        current.text.push(next);
+                        index += 1;
+                    }
+                }
+                '\'' | '"' => {
+                    word.get_or_insert_with(|| Word { start: at, ..Word::default() });
+                    scan.mark(at, at + 1, Role::String);
+                    quote = Some((char, at));
+                }
+                '$' if next == Some('(') => {
+                    // a command starts inside; its words get their own colours
+                    if let Some(current) = word.as_mut() {
+                        current.expands = true;
+                    }
+                    self.end_word(word.take(), at, &mut command, &mut target, &mut scan);
+                    brackets.push(('(', at + 1));
+                    index += 1;
+                    scan.mark(at, at + 2, Role::Operator);
+                    command = true;
+                }
+                '$' => {
+                    let current = word.get_or_insert_with(|| Word { start: at, ..Word::default() });
+                    current.expands = true;
+                    index = Self::variable(&chars, index, &mut scan);
+                }
+                '<' | '>' => {
+                    // a descriptor number before it is part of the redirection
+                    match word.take() {
+                        Some(number) if !number.text.is_empty() && number.text.chars().all(|char| char.is_ascii_digit()) => {
+                            scan.mark(number.start, at, Role::Redirect);
+                        }
+                        other => self.end_word(other, at, &mut command, &mut target, &mut scan),
+                    }
+                    while chars.get(index).is_some_and(|(_, char)| matches!(char, '>' | '<' | '&' | '|')) {
+                        index += 1;
+                    }
+                    // `>&2` and `<&-` name a descriptor, not a file
+                    if chars[index - 1].1 == '&' {
+                        while chars.get(index).is_some_and(|(_, char)| char.is_ascii_digit() || *char == '-') {
+                            index += 1;
+                        }
+                        target = false;
+                        scan.mark(at, end_of(index), Role::Redirect);
+                        continue;
+                    }
+                    scan.mark(at, end_of(index), Role::Redirect);
+                    target = true;
+                }
+                '&' if next == Some('>') => {
+                    self.end_word(word.take(), at, &mut command, &mut target, &mut scan);
+                    index += 1;
+                    scan.mark(at, end_of(index), Role::Redirect);
+                    target = true;
+                }
+                ';' | '|' | '&' | '(' | ')' => {
+                    self.end_word(word.take(), at, &mut command, &mut target, &mut scan);
+                    if next == Some(char) && char != '(' && char != ')' {
+                        index += 1;
+                    }
+                    scan.mark(at, end_of(index), Role::Operator);
+                    match char {
+                        '(' => brackets.push((char, at)),
+                        ')' => Self::close(&mut brackets, '(', at, &mut scan),
+                        _ => {}
+                    }
+                    command = char != ')';
+                }
+                _ => {
+                    let current = word.get_or_insert_with(|| Word { start: at, ..Word::default() });
+                    current.text.push(char);
+                    match char {
+                        '[' | '{' => brackets.push((char, at)),
+                        ']' => Self::close(&mut brackets, '[', at, &mut scan),
+                        '}' => Self::close(&mut brackets, '{', at, &mut scan),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        self.end_word(word, line.len(), &mut command, &mut target, &mut scan);
+        scan
+    }
+
+    /// Pairs a closing bracket with the open one, when that is the innermost
+    fn close(brackets: &mut Vec<(char, usize)>, open: char, at: usize, scan: &mut Scan) {
+        if brackets.last().is_some_and(|(char, _)| *char == open) {
+            let (_, opened_at) = brackets.pop().unwrap_or_default();
+            scan.pairs.push((opened_at, at));
+        }
+    }
+
+    /// Marks the variable whose `$` is just before `index`: `$name`, `${...}`
+    /// or a special one like `$?`. Returns the index after it.
+    fn variable(chars: &[(usize, char)], mut index: usize, scan: &mut Scan) -> usize {
+        let dollar = chars[index - 1].0;
+        let len = scan.roles.len();
+        let end_of = |index: usize| chars.get(index).map_or(len, |(at, _)| *at);
+        match chars.get(index).map(|(_, char)| *char) {
+            Some('{') => {
+                let open = chars[index].0;
+                match chars[index..].iter().position(|(_, char)| *char == '}') {
+                    Some(offset) => {
+                        scan.pairs.push((open, chars[index + offset].0));
+                        index += offset + 1;
+                    }
+                    None => index = chars.len(),
+                }
+            }
+            Some(char) if char.is_alphabetic() || char == '_' => {
+                while chars.get(index).is_some_and(|(_, char)| char.is_alphanumeric() || *char == '_') {
+                    index += 1;
+                }
+            }
+            Some(char) if char.is_ascii_digit() || "?$#!@*-".contains(char) => index += 1,
+            _ => {}
+        }
+        scan.mark(dollar, end_of(index), Role::Variable);
+        index
+    }
+
+    /// Colours a word that ends at `end`: a command word by whether it
+    /// resolves, any other word as a path when it names one that exists
+    fn end_word(&mut self, word: Option<Word>, end: usize, command: &mut bool, target: &mut bool, scan: &mut Scan) {
+        let word = match word {
+            Some(word) => word,
+            None => return,
+        };
+        if *command && !*target {
+            *command = false;
+            if !word.expands {
+                let role = match self.resolves(&word.text) {
+                    true => Role::Command,
+                    false => Role::Error,
+                };
+                scan.fill(word.start, end, role);
+            }
+            return;
+        }
+        *target = false;
+        if !word.expands && !word.text.is_empty() && CURRENT_DIR.lock().unwrap().join(&word.text).exists() {
+            scan.paths.push((word.start, end));
+        }
+    }
+
+    fn resolves(&mut self, name: &str) -> bool {
+        if let Some(found) = self.commands.get(name) {
+            return *found;
+        }
+        let found = BUILTINS.get(name).is_some()
+            || ALIASES.lock().unwrap().contains_key(name)
+            || Plugin::find(name).is_some()
+            || ExecutableCmds::exists(name, &CURRENT_DIR.lock().unwrap());
+        self.commands.insert(name.to_string(), found);
+        found
+    }
+}
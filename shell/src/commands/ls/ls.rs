@@ -1,5 +1,8 @@
 use std::fs::ReadDir;
 use std::path::PathBuf;
+use crate::commands::builtin::{Builtin, Context, OptionSpec};
+use crate::print_error;
+use crate::utils::DisplayWidth;
 
 pub struct LsCommand {
     format: Vec<LsFormat>,
@@ -31,8 +34,8 @@ impl LsFormat {
 }
 #[allow(dead_code)]
 impl LsCommand {
-    pub fn new(dir: &PathBuf, args: &Vec<String>) -> Result<Self, Box<dyn std::error::Error>> {
-        let args = args.clone();
+    pub fn new(dir: &PathBuf, args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let args = args.to_vec();
         let temp = Self {
             dirs: Vec::new(),
             format: Vec::new(),
@@ -94,44 +97,48 @@ impl LsCommand {
         // println!("row = {} | column = {}", row_len, column_len);
         let longest_dir_name = self.find_longest_item_name(target_dir)? + 7;
         // println!("longest_dir_name = {}", longest_dir_name);
-        let max_column = row_len / longest_dir_name;
-        // println!("max_column = {}", max_column);
-        let mut counter = 0;
-        let mut line = String::new();
         if target_dir.is_some() {
             println!("{}:", target_dir.unwrap().to_string_lossy());
         }
+        let mut names = Vec::new();
         for dir in entry {
             let dir = dir?.file_name().to_string_lossy().to_string();
             if dir.starts_with('.') {
                 continue;
             }
-            line.push_str(&self.row_item_builder(
-                &dir,
-                longest_dir_name,
-                counter == max_column - 1,
-            ));
-            counter += 1;
-            if counter == max_column {
-                println!("{}", line);
-                line.clear();
-                counter = 0;
-            }
+            names.push(dir);
         }
-        if line.len() > 0 {
+        for line in Self::columns(&names, longest_dir_name, row_len) {
             println!("{}", line);
         }
         Ok(())
     }
 
-    fn row_item_builder(&self, dir_r_file: &str, column_len: u16, is_last: bool) -> String {
+    /// Lays `items` out in rows of `column_len` wide columns that fit in
+    /// `width`, as `ls` prints a directory. Tab completion lists its
+    /// candidates the same way.
+    pub fn columns(items: &[String], column_len: u16, width: u16) -> Vec<String> {
+        let max_column = (width / column_len.max(1)).max(1) as usize;
+        items
+            .chunks(max_column)
+            .map(|row| {
+                let mut line = String::new();
+                for (counter, item) in row.iter().enumerate() {
+                    line.push_str(&Self::row_item_builder(item, column_len, counter == max_column - 1));
+                }
+                line
+            })
+            .collect()
+    }
+
+    fn row_item_builder(dir_r_file: &str, column_len: u16, is_last: bool) -> String {
         if is_last {
             // DebugTool::print(format!("last_item = {}", dir_r_file));
             return dir_r_file.into();
         }
         let mut dir_r_file = dir_r_file.to_string();
         // println!("column_len = {} | dir_file_len = {}", column_len, dir_r_file.len());
-        let diff_len = column_len - dir_r_file.len() as u16;
+        let diff_len = column_len.saturating_sub(dir_r_file.display_width() as u16);
         for _ in 0..diff_len {
             dir_r_file.push(' ');
         }
@@ -156,7 +163,7 @@ impl LsCommand {
         for dir_result in entry {
             if let Ok(dir_entry) = dir_result {
                 let file_name = dir_entry.file_name().to_string_lossy().to_string();
-                let name_len = file_name.len();
+                let name_len = file_name.display_width();
                 if name_len > longest_dir_name {
                     longest_dir_name = name_len;
                     // longest_file = file_name;
@@ -193,3 +200,42 @@ impl LsCommand {
         Ok(())
     }
 }
+
+pub struct LsBuiltin;
+
+impl Builtin for LsBuiltin {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn help(&self) -> &'static str {
+        "ls [-l1FRx] [dir]\nList the files in a directory."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[
+            OptionSpec { flag: "-l", help: "long listing" },
+            OptionSpec { flag: "-1", help: "one entry per line" },
+            OptionSpec { flag: "-F", help: "mark directories and executables" },
+            OptionSpec { flag: "-R", help: "list subdirectories recursively" },
+            OptionSpec { flag: "-x", help: "list entries across rather than down" },
+        ]
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        let mut ls = match LsCommand::new(&ctx.current_dir, args) {
+            Ok(ls) => ls,
+            Err(err) => {
+                print_error!("{}", err.to_string());
+                return Ok(2);
+            }
+        };
+        match ls.run() {
+            Ok(_) => Ok(0),
+            Err(err) => {
+                print_error!("{}", err.to_string());
+                Ok(1)
+            }
+        }
+    }
+}
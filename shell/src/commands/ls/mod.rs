@@ -1,3 +1,3 @@
 mod ls;
 mod ls_args;
-pub use ls::LsCommand;
\ No newline at end of file
+pub use ls::{LsBuiltin, LsCommand};
\ No newline at end of file
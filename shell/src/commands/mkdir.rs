@@ -1,4 +1,6 @@
 use std::path::PathBuf;
+use crate::commands::builtin::{Builtin, Context};
+use crate::print_error;
 
 pub struct MkdirCmd {
     name: String,
@@ -7,7 +9,7 @@ pub struct MkdirCmd {
 
 impl MkdirCmd {
     pub fn new(
-        name: &Vec<String>,
+        name: &[String],
         current_dir: &PathBuf,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let name = match name.first() {
@@ -37,3 +39,32 @@ impl MkdirCmd {
         }
     }
 }
+
+pub struct MkdirBuiltin;
+
+impl Builtin for MkdirBuiltin {
+    fn name(&self) -> &'static str {
+        "mkdir"
+    }
+
+    fn help(&self) -> &'static str {
+        "mkdir name\nCreate a directory."
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        let mkdir = match MkdirCmd::new(args, &ctx.current_dir) {
+            Ok(mkdir) => mkdir,
+            Err(err) => {
+                print_error!("mkdir: {}", err.to_string());
+                return Ok(2);
+            }
+        };
+        match mkdir.run() {
+            Ok(_) => Ok(0),
+            Err(err) => {
+                print_error!("{}", err.to_string());
+                Ok(1)
+            }
+        }
+    }
+}
@@ -6,7 +6,32 @@ pub(crate) mod simple;
 mod ls;
 mod cd;
 mod mkdir;
-mod executable_cmds;
+pub(crate) mod executable_cmds;
+mod set;
+mod trap;
+mod hash;
+mod exec;
+mod command;
+mod type_cmd;
+mod alias;
+pub(crate) mod builtin;
+mod help;
+mod plugin;
+mod completion;
+mod complete;
+mod highlight;
+mod highlighting;
 
 pub use exit::*;
-pub use parse_cmd::CmdParser;
\ No newline at end of file
+pub use parse_cmd::CmdParser;
+pub use set::ShellOptions;
+pub use trap::Traps;
+pub use hash::CommandHash;
+pub use plugin::PluginCache;
+pub use exec::ExecCommand;
+pub use alias::Aliases;
+pub use builtin::Registry;
+pub use completion::Completion;
+pub use complete::CompSpec;
+pub use highlight::Theme;
+pub use highlighting::Highlighter;
\ No newline at end of file
@@ -1,189 +1,193 @@
 use regex::Regex;
+use crate::commands::builtin::Context;
 use crate::commands::commands::Commands;
-use crate::commands::cd::CdCommand;
-use crate::commands::echo::EchoCommand;
-use crate::commands::ls::LsCommand;
-use crate::commands::mkdir::MkdirCmd;
-use crate::commands::simple::ClearCommand;
-use crate::utils::{WordSplitter};
-use crate::{print_error, print_success, CURRENT_DIR};
-use crate::commands::executable_cmds::ExecutableCmds;
-use crate::commands::ExitCommand;
-use crate::interpreter::{eval, tokenize, Interpreter, Parser};
+use crate::commands::alias::Aliases;
+use crate::commands::executable_cmds::{ExecError, ExecOutcome, ExecutableCmds};
+use crate::commands::trap::Traps;
+use crate::utils::WordSplitter;
+use crate::{print_error, print_success, CURRENT_DIR, LAST_STATUS, SHELL_OPTIONS};
+use crate::interpreter::{eval, Expander, Interpreter, Interrupted, ListSplitter, Parser, SyntaxError};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 
 
 
 pub struct CmdParser {
     cmd: Commands,
+    is_list: bool,
     args: Vec<String>,
     script_line: String,
     is_expression: bool,
+    /// Aliases expanded into `script_line`, kept from expanding again while
+    /// it runs as a list
+    aliases: Vec<String>,
 }
 
 impl Default for CmdParser {
     fn default() -> Self {
         Self {
+            is_list: false,
             is_expression: false,
             cmd: Commands::EmptyCommand,
             args: vec![],
             script_line: String::new(),
+            aliases: vec![],
         }
     }
 }
 
 impl CmdParser {
     pub fn new(
-        text_line: &String,
+        text_line: &str,
     ) -> Result<CmdParser, Box<dyn std::error::Error>> {
-        use crate::commands::commands::Commands::*;
         if text_line.is_empty() {
+            return Ok(CmdParser::default());
+        }
+        let (text_line, aliases) = Aliases::expand(text_line);
+        let text_line = &text_line;
+        // lists are split and expanded one command at a time, so that `$?`
+        // sees the status of the command before it
+        if ListSplitter::is_list(text_line) {
             return Ok(CmdParser {
-                is_expression: false,
-                cmd: EmptyCommand,
-                args: vec![],
-                script_line: String::new(),
+                is_list: true,
+                script_line: text_line.clone(),
+                aliases,
+                ..Default::default()
             });
         }
-        let parts = text_line.advance_split();
-        let cmd = parts[0].to_string();
-        let args = parts[1..].to_vec().iter().filter(|x| !x.is_empty()).map(|x| x.to_string()).collect::<Vec<String>>();
+        let parts = Expander::expand_line(text_line)?.advance_split();
+        let cmd = parts.first().cloned().unwrap_or_default();
+        let args = parts.iter().skip(1).filter(|x| !x.is_empty()).map(|x| x.to_string()).collect::<Vec<String>>();
         let cmd = Commands::new(&cmd)?;
 
-        let regex = Regex::new(r"-?\d+\.\d+|-?\d+|[+\-*/()]").unwrap();
+        // only a word made of numbers and operators, e.g. `(1+2)*3`
+        let regex = Regex::new(r"^[-+*/().\d\s]*\d[-+*/().\d\s]*$").unwrap();
 
         if regex.is_match(&cmd.get_cmd()) {
             return Ok(CmdParser {
+                is_list: false,
                 is_expression: true,
                 cmd,
                 args,
                 script_line: text_line.clone(),
+                aliases,
             });
         }
 
         Ok(CmdParser {
+            is_list: false,
             is_expression: false,
             cmd,
             args,
             script_line: text_line.clone(),
+            aliases,
         })
     }
 
-    pub fn execute_cmd(self) -> Result<(Self), Box<dyn std::error::Error>> {
-        let mut current_dir = CURRENT_DIR.lock().unwrap();
+    pub fn execute_cmd(self) -> Result<i32, Box<dyn std::error::Error>> {
+        let status = if self.is_list {
+            Aliases::while_active(&self.aliases, || Interpreter::run_line(&self.script_line))?
+        } else {
+            if SHELL_OPTIONS.lock().unwrap().xtrace {
+                self.trace();
+            }
+            self.run_single()?
+        };
+        LAST_STATUS.store(status, Ordering::SeqCst);
+        Ok(status)
+    }
+
+    /// Runs a file as a cotsh script. Options and traps set by the script
+    /// must not leak into this shell, and the script starts without ours.
+    pub(crate) fn run_script(script: PathBuf) -> i32 {
+        let interpreter = match Interpreter::new(script.clone()) {
+            Ok(interpreter) => interpreter,
+            Err(err) => {
+                print_error!("cotsh: {}: {}", script.display(), err.to_string());
+                return 127;
+            }
+        };
+        let options = *SHELL_OPTIONS.lock().unwrap();
+        let traps = Traps::replace_all(Default::default());
+        // `interpret` has already reported the error with its location
+        let status = match interpreter.interpret() {
+            Ok(status) => status,
+            Err(err) if err.is::<SyntaxError>() => 2,
+            Err(err) if err.is::<Interrupted>() => 130,
+            Err(_) => 1,
+        };
+        let status = Traps::run_exit(status);
+        Traps::replace_all(traps);
+        *SHELL_OPTIONS.lock().unwrap() = options;
+        status
+    }
+
+    /// `set -x`: prints the expanded command to stderr, prefixed with `$PS4`
+    fn trace(&self) {
+        let ps4 = std::env::var("PS4").unwrap_or_else(|_| "+ ".to_string());
+        let line = if self.is_expression {
+            self.script_line.clone()
+        } else {
+            std::iter::once(self.cmd.get_cmd()).chain(self.args.iter().cloned()).collect::<Vec<_>>().join(" ")
+        };
+        eprintln!("{}{}", ps4, line);
+    }
+
+    /// Runs an already resolved command, the way `command` and `builtin`
+    /// hand theirs back to the dispatcher
+    pub(crate) fn run_resolved(cmd: Commands, args: Vec<String>) -> Result<i32, Box<dyn std::error::Error>> {
+        CmdParser { cmd, args, ..Default::default() }.run_single()
+    }
+
+    /// Runs one simple command and returns its exit status. Builtins follow
+    /// the usual convention: 0 on success, 1 on failure, 2 on misuse.
+    /// `exit` comes back as an `ErrExit` error for the caller to act on.
+    fn run_single(&self) -> Result<i32, Box<dyn std::error::Error>> {
         use crate::commands::commands::Commands::*;
 
         if self.is_expression{
             let res = Parser::new(&self.script_line).parse_expression(0) ;
             print_success!("Result: {}", eval(&res));
-            return Ok(self);
+            return Ok(0);
         }
 
+        let status = match &self.cmd {
+            BuildIn(builtin) => builtin.run(&mut Context::new(), &self.args)?,
 
-
-        if self.script_line.contains(";") || self.script_line.contains("&&") || self.script_line.contains("||") {
-            match Interpreter::new_with_lines(&self.script_line).interpret() {
-                Ok(_) => {}
+            Plugin(plugin) => match plugin.run(&mut Context::new(), &self.args) {
+                Ok(status) => status,
                 Err(err) => {
-                    print_error!("{}", err.to_string());
+                    print_error!("cotsh: {}", err.to_string());
+                    1
                 }
-            }
-            return Ok(self);
-        }
-
-        match &self.cmd {
-
-            Exit(_) => {
-                match ExitCommand::exit(self.args.first()){
-                    Ok(_) => {}
-                    Err(err) => {
-                        print_error!("{}", err.to_string());
-                    }
-                };
-            },
-
-            Type(_cmd) | Which(_cmd) => {
-                match self.cmd.type_cmd(&self.args[0]) {
-                    Ok(res) => print_success!("{}", res),
-                    Err(err) => print_error!("{}", err.to_string()),
-                };
-            }
-
-            Echo(_) => match EchoCommand::new(self.args.clone(), &current_dir).run() {
-                Ok(_) => {}
-                Err(err) => print_error!("echo: {}", err.to_string())
             },
 
-            Clear(_) => match ClearCommand::run() {
-                Ok(_) => {}
-                Err(err) => {
-                    print_error!("{}", err.to_string());
-                }
-            },
-
-            Ls(_) => match LsCommand::new(&current_dir, &self.args) {
-                Ok(mut res) => match res.run() {
-                    Ok(_) => {}
-                    Err(err) => {
-                        print_error!("{}", err.to_string());
-                    }
-                },
-                Err(err) => {
-                    print_success!("{}", err.to_string());
-                }
-            },
-            Cd(_) => {
-                *current_dir = match CdCommand::new(&current_dir, &self.args) {
-                    Ok(res) => match res.run() {
-                        Ok(res) => res,
-                        Err(err) => {
-                            print_error!("{}", err.to_string());
-                            return Ok(self);
-                        }
-                    },
+            NotBuildIn(res) => {
+                let current_dir = CURRENT_DIR.lock().unwrap().clone();
+                let args = self.args.iter().map(|arg| arg.unquote()).collect();
+                match res.with_args(args, &current_dir).execute_cmd() {
+                    Ok(ExecOutcome::Status(status)) => status,
+                    Ok(ExecOutcome::Script(path)) => Self::run_script(path),
                     Err(err) => {
-                        print_error!("{}", err.0.to_string());
-                        err.1
+                        print_error!("cotsh: {}: {}", self.cmd.get_cmd(), err.to_string());
+                        err.downcast_ref::<ExecError>().map(ExecError::status).unwrap_or(126)
                     }
                 }
             }
-            Pwd(_) => print_success!("{}", &current_dir.display()),
-            Mkdir(_) => match MkdirCmd::new(&self.args, &current_dir) {
-                Ok(res) => match res.run() {
-                    Ok(_) => {}
-                    Err(err) => {
-                        print_success!("{}", err.to_string());
-                    }
-                },
-                Err(_err) => {}
-            },
 
-            NotBuildIn(_) => {
-                match ExecutableCmds::new(&self.cmd.get_cmd(), &self.args, &current_dir){
-                    Ok(res) => match res.execute_cmd() {
-                        Ok(_) => {}
-                        Err(err) => {
-                            print_error!("{}", err.to_string());
-                        }
-                    },
-                    Err(err) => {
-                        print_error!("{}", err.to_string());
-                    }
-                }
-            }
+            EmptyCommand => 0,
 
-            Cotsh(_) => {
-                match Interpreter::new_with_lines(&self.script_line).interpret() {
-                    Ok(_) => {}
-                    Err(err) => {
-                        print_error!("{}", err.to_string());
-                    }
+            InvalidCmd(cmd) => match ExecutableCmds::resolve(cmd, &CURRENT_DIR.lock().unwrap()) {
+                Err(ExecError::NotFound) | Ok(_) => {
+                    print_error!("cotsh: command not found: {}", cmd);
+                    127
+                }
+                Err(err) => {
+                    print_error!("cotsh: {}: {}", cmd, err);
+                    err.status()
                 }
-            }
-
-            _ => {
-                print_error!("cotsh: command not found: {}", self.cmd.get_cmd());
             },
-        }
-        Ok(self)
+        };
+        Ok(status)
     }
 }
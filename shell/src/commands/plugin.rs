@@ -0,0 +1,185 @@
+//! Plugins are executables named `cotsh-<name>` in
+//! `$XDG_CONFIG_HOME/cotsh/plugins`, run as the command `<name>` with a JSON
+//! request on stdin and an optional JSON response on stdout. The protocol is
+//! described in the README.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::Ordering;
+use serde::{Deserialize, Serialize};
+use crate::commands::builtin::Context;
+use crate::commands::executable_cmds::ExecutableCmds;
+use crate::utils::{Dirs, JobControl, WordSplitter};
+use crate::{LAST_STATUS, PLUGINS};
+
+const PREFIX: &str = "cotsh-";
+const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Request<'a> {
+    version: u32,
+    name: &'a str,
+    args: Vec<String>,
+    cwd: &'a Path,
+    env: BTreeMap<String, String>,
+    status: i32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Response {
+    status: Option<i32>,
+    output: Option<String>,
+    error: Option<String>,
+    cd: Option<PathBuf>,
+    set: BTreeMap<String, Option<String>>,
+}
+
+pub struct Plugin {
+    name: String,
+    path: PathBuf,
+}
+
+/// The plugins found in the plugin directory, by name. The directory is
+/// read once and again when `XDG_CONFIG_HOME` moves it or after `hash -r`,
+/// so resolving a command word costs no file system access.
+#[derive(Default)]
+pub struct PluginCache {
+    dir: Option<PathBuf>,
+    paths: Option<BTreeMap<String, PathBuf>>,
+}
+
+impl PluginCache {
+    fn paths(&mut self) -> &BTreeMap<String, PathBuf> {
+        let dir = Plugin::dir();
+        if self.dir != dir {
+            self.paths = None;
+            self.dir = dir;
+        }
+        let dir = self.dir.as_deref();
+        self.paths.get_or_insert_with(|| Self::read(dir))
+    }
+
+    fn read(dir: Option<&Path>) -> BTreeMap<String, PathBuf> {
+        let entries = match dir.and_then(|dir| std::fs::read_dir(dir).ok()) {
+            Some(entries) => entries,
+            None => return BTreeMap::new(),
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().strip_prefix(PREFIX)?.to_string();
+                let path = entry.path();
+                ExecutableCmds::is_executable(&path).then_some((name, path))
+            })
+            .filter(|(name, _)| !name.is_empty())
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.paths = None;
+    }
+}
+
+impl Plugin {
+    fn dir() -> Option<PathBuf> {
+        Dirs::config_dir().map(|dir| dir.join("plugins"))
+    }
+
+    /// The plugin run for the command `name`, if one is installed
+    pub fn find(name: &str) -> Option<Self> {
+        let path = PLUGINS.lock().unwrap().paths().get(name)?.clone();
+        Some(Self { name: name.to_string(), path })
+    }
+
+    /// Every installed plugin, sorted by name
+    pub fn all() -> Vec<Self> {
+        PLUGINS.lock().unwrap().paths().iter()
+            .map(|(name, path)| Self { name: name.clone(), path: path.clone() })
+            .collect()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.unquote()).collect();
+        let request = serde_json::to_string(&Request {
+            version: PROTOCOL_VERSION,
+            name: &self.name,
+            args: args.clone(),
+            cwd: &ctx.current_dir,
+            env: std::env::vars().collect(),
+            status: LAST_STATUS.load(Ordering::SeqCst),
+        })?;
+
+        let mut command = Command::new(&self.path);
+        command
+            .args(&args)
+            .current_dir(&ctx.current_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        JobControl::prepare(&mut command);
+        let mut child = command.spawn().map_err(|err| format!("{}: {}", self.name, err))?;
+        // written from another thread: a plugin may answer before it has
+        // read the whole request, and either side can fill its pipe
+        let stdin = child.stdin.take();
+        let writer = std::thread::spawn(move || {
+            // a plugin that exits without reading its request is not an error
+            if let Some(mut stdin) = stdin {
+                let _ = stdin.write_all(request.as_bytes());
+            }
+        });
+        let mut output = String::new();
+        let read = match child.stdout.take() {
+            Some(mut stdout) => stdout.read_to_string(&mut output).map(|_| ()),
+            None => Ok(()),
+        };
+        let code = JobControl::wait(child)?;
+        let _ = writer.join();
+        read?;
+
+        if output.trim().is_empty() {
+            return Ok(code);
+        }
+        let response: Response = serde_json::from_str(&output)
+            .map_err(|err| format!("{}: invalid plugin response: {}", self.name, err))?;
+        self.apply(ctx, response, code)
+    }
+
+    fn apply(&self, ctx: &mut Context, response: Response, code: i32) -> Result<i32, Box<dyn std::error::Error>> {
+        if let Some(output) = response.output {
+            print!("{}", output);
+            std::io::stdout().flush()?;
+        }
+        if let Some(error) = response.error {
+            eprint!("{}", error);
+        }
+        for (name, value) in response.set {
+            // SAFETY: the request writer has been joined; the shell is
+            // single threaded again
+            unsafe {
+                match value {
+                    Some(value) => std::env::set_var(&name, value),
+                    None => std::env::remove_var(&name),
+                }
+            }
+        }
+        if let Some(dir) = response.cd {
+            let dir = ctx.current_dir.join(dir);
+            if !dir.is_dir() {
+                return Err(format!("{}: cd: {}: not a directory", self.name, dir.display()).into());
+            }
+            ctx.chdir(dir.canonicalize()?);
+        }
+        Ok(response.status.unwrap_or(code))
+    }
+}
@@ -0,0 +1,146 @@
+use crate::commands::builtin::{status, Builtin, Context, OptionSpec};
+use crate::{print_success, SHELL_OPTIONS};
+
+/// Long option names in the order `set -o` lists them, with their short flag
+const OPTIONS: [(&str, Option<char>); 7] = [
+    ("emacs", None),
+    ("errexit", Some('e')),
+    ("noclobber", Some('C')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("vi", None),
+    ("xtrace", Some('x')),
+];
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ShellOptions {
+    pub errexit: bool,
+    pub noclobber: bool,
+    pub nounset: bool,
+    /// Accepted so `set -euo pipefail` works, and does nothing: there are
+    /// no pipelines yet
+    pub pipefail: bool,
+    /// vi editing at the prompt; `emacs` is the same option turned off
+    pub vi: bool,
+    pub xtrace: bool,
+}
+
+impl ShellOptions {
+    fn flag_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "errexit" => Some(&mut self.errexit),
+            "noclobber" => Some(&mut self.noclobber),
+            "nounset" => Some(&mut self.nounset),
+            "pipefail" => Some(&mut self.pipefail),
+            "vi" => Some(&mut self.vi),
+            "xtrace" => Some(&mut self.xtrace),
+            _ => None,
+        }
+    }
+
+    fn get(mut self, name: &str) -> bool {
+        if name == "emacs" {
+            return !self.vi;
+        }
+        self.flag_mut(name).is_some_and(|flag| *flag)
+    }
+
+    /// Applies `set` arguments, `[-+eCux] [-+o name]...`. Returns whether a
+    /// `-o` or `+o` without a name asked for the options to be listed, and
+    /// which way: `Some(true)` for `+o`.
+    pub fn apply(&mut self, args: &[String]) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        let mut list = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let on = match arg.chars().next() {
+                Some('-') => true,
+                Some('+') => false,
+                _ => return Err(format!("set: {}: invalid option", arg).into()),
+            };
+            for flag in arg.chars().skip(1) {
+                let name = match flag {
+                    'o' => match args.next() {
+                        Some(name) => name.as_str(),
+                        None => {
+                            list = Some(!on);
+                            continue;
+                        }
+                    },
+                    _ => match OPTIONS.iter().find(|(_, short)| *short == Some(flag)) {
+                        Some((name, _)) => name,
+                        None => return Err(format!("set: -{}: invalid option", flag).into()),
+                    },
+                };
+                if name == "emacs" {
+                    self.vi = !on;
+                    continue;
+                }
+                match self.flag_mut(name) {
+                    Some(value) => *value = on,
+                    None => return Err(format!("set: {}: invalid option name", name).into()),
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    /// `set -o` lists a table, `set +o` the commands that recreate the state
+    pub fn listing(self, as_commands: bool) -> Vec<String> {
+        OPTIONS
+            .iter()
+            .map(|(name, _)| {
+                let on = self.get(name);
+                match as_commands {
+                    true => format!("set {}o {}", if on { '-' } else { '+' }, name),
+                    false => format!("{:<15} {}", name, if on { "on" } else { "off" }),
+                }
+            })
+            .collect()
+    }
+}
+
+pub struct SetCommand;
+
+impl Builtin for SetCommand {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn help(&self) -> &'static str {
+        "set [-+eCux] [-+o name] ...\nTurn shell options on with - or off with +; list them with no arguments.\n\
+         pipefail is accepted so `set -euo pipefail` works, but has no effect: there are no pipelines yet."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[
+            OptionSpec { flag: "-e", help: "exit when a command fails (errexit)" },
+            OptionSpec { flag: "-C", help: "don't overwrite files with > (noclobber)" },
+            OptionSpec { flag: "-u", help: "treat unset variables as an error (nounset)" },
+            OptionSpec { flag: "-x", help: "print commands before running them (xtrace)" },
+            OptionSpec { flag: "-o", help: "set an option by name, or list them" },
+        ]
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        Ok(status(Self::set(args), 2))
+    }
+}
+
+impl SetCommand {
+    /// `set [-+eCux] [-+o name]...`; `set -o` and `set +o` list the options.
+    /// Nothing changes when an argument is invalid.
+    fn set(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut options = *SHELL_OPTIONS.lock().unwrap();
+        let list = match args.is_empty() {
+            true => Some(false),
+            false => options.apply(args)?,
+        };
+        *SHELL_OPTIONS.lock().unwrap() = options;
+        if let Some(as_commands) = list {
+            for line in options.listing(as_commands) {
+                print_success!("{}", line);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,13 @@
 use crossterm::cursor::MoveTo;
+use crate::commands::builtin::{status, Builtin, Context};
+use crate::commands::CmdParser;
+use crate::utils::WordSplitter;
+use crate::{print_error, print_success};
 
 pub struct ClearCommand;
 
 impl ClearCommand {
-    pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    pub fn clear() -> Result<(), Box<dyn std::error::Error>> {
         use crossterm::{execute, terminal};
         use std::io::stdout;
 
@@ -17,3 +21,55 @@ impl ClearCommand {
     }
 }
 
+impl Builtin for ClearCommand {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn help(&self) -> &'static str {
+        "clear\nClear the terminal screen."
+    }
+
+    fn run(&self, _ctx: &mut Context, _args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        Ok(status(Self::clear(), 1))
+    }
+}
+
+pub struct PwdCommand;
+
+impl Builtin for PwdCommand {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn help(&self) -> &'static str {
+        "pwd\nPrint the shell's working directory."
+    }
+
+    fn run(&self, ctx: &mut Context, _args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        print_success!("{}", ctx.current_dir.display());
+        Ok(0)
+    }
+}
+
+pub struct CotshCommand;
+
+impl Builtin for CotshCommand {
+    fn name(&self) -> &'static str {
+        "cotsh"
+    }
+
+    fn help(&self) -> &'static str {
+        "cotsh script\nRun a cotsh script with its own options and traps."
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        match args.first() {
+            Some(script) => Ok(CmdParser::run_script(ctx.current_dir.join(script.unquote()))),
+            None => {
+                print_error!("cotsh: usage: cotsh <script>");
+                Ok(2)
+            }
+        }
+    }
+}
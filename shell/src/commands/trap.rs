@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::commands::builtin::{status, Builtin, Context, OptionSpec};
+use crate::interpreter::{ErrExit, Interpreter};
+use crate::utils::{JobControl, Signals, WordSplitter};
+use crate::{print_error, print_success, LAST_STATUS, TRAPS};
+
+/// Pseudo-signals run by the interpreter rather than delivered by the kernel
+const PSEUDO_SIGNALS: [&str; 3] = ["EXIT", "ERR", "DEBUG"];
+
+/// Signals that end the shell when nothing traps them. They are caught
+/// while an EXIT trap is set, so that it still runs.
+const ENDING_SIGNALS: [&str; 3] = ["HUP", "INT", "TERM"];
+
+/// Set while a DEBUG, ERR or signal handler runs, so handlers don't trigger
+/// themselves
+static IN_TRAP: AtomicBool = AtomicBool::new(false);
+
+/// The trap table: handlers keyed by `EXIT`, `ERR`, `DEBUG` or a signal name.
+/// An empty handler ignores the signal.
+pub struct Traps;
+
+impl Traps {
+    /// `EXIT`/`0`, `ERR`, `DEBUG` or anything `Signals::number` accepts
+    fn canonical(name: &str) -> Option<String> {
+        let upper = name.to_uppercase();
+        if upper == "0" || upper == "EXIT" {
+            return Some("EXIT".to_string());
+        }
+        if PSEUDO_SIGNALS.contains(&upper.as_str()) {
+            return Some(upper);
+        }
+        Signals::number(name).and_then(Signals::name).map(String::from)
+    }
+
+    fn set(name: String, action: String) {
+        if let Some(signal) = Signals::number(&name) {
+            match action.is_empty() {
+                true => Signals::ignore(signal),
+                false => Signals::catch(signal),
+            }
+        }
+        let is_exit = name == "EXIT";
+        TRAPS.lock().unwrap().insert(name, action);
+        if is_exit {
+            Self::restore_ending();
+        }
+    }
+
+    fn reset(name: &str) {
+        TRAPS.lock().unwrap().remove(name);
+        if let Some(signal) = Signals::number(name) {
+            Self::restore(signal);
+        }
+        if name == "EXIT" {
+            Self::restore_ending();
+        }
+    }
+
+    /// The disposition a signal has without a trap. The interactive shell
+    /// keeps its own Ctrl-C handler, and the ending signals are caught while
+    /// there is an EXIT trap to run.
+    fn restore(signal: i32) {
+        let name = Signals::name(signal).unwrap_or_default();
+        if name == "INT" && JobControl::is_interactive() {
+            JobControl::catch_interrupt();
+        } else if ENDING_SIGNALS.contains(&name) && Self::get("EXIT").is_some() {
+            Signals::catch(signal);
+        } else {
+            Signals::reset(signal);
+        }
+    }
+
+    /// Follows the EXIT trap being set or removed
+    fn restore_ending() {
+        for name in ENDING_SIGNALS {
+            if let Some(signal) = Signals::number(name).filter(|_| Self::get(name).is_none()) {
+                Self::restore(signal);
+            }
+        }
+    }
+
+    pub fn get(name: &str) -> Option<String> {
+        TRAPS.lock().unwrap().get(name).cloned()
+    }
+
+    /// Swaps the whole table, e.g. to give a script a clean one, and returns
+    /// the previous table so it can be put back afterwards
+    pub fn replace_all(traps: BTreeMap<String, String>) -> BTreeMap<String, String> {
+        let old = std::mem::take(&mut *TRAPS.lock().unwrap());
+        for name in old.keys() {
+            Self::reset(name);
+        }
+        for (name, action) in traps {
+            Self::set(name, action);
+        }
+        old
+    }
+
+    /// Runs a handler with `$?` preserved around it. An `exit` inside the
+    /// handler comes back as `ErrExit`; other errors are reported here.
+    fn run(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let action = match Self::get(name) {
+            Some(action) if !action.is_empty() => action,
+            _ => return Ok(()),
+        };
+        if IN_TRAP.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let status = LAST_STATUS.load(Ordering::SeqCst);
+        let res = Interpreter::run_line(&action);
+        LAST_STATUS.store(status, Ordering::SeqCst);
+        IN_TRAP.store(false, Ordering::SeqCst);
+        match res {
+            Err(err) if err.is::<ErrExit>() => Err(err),
+            Err(err) => {
+                print_error!("cotsh: trap {}: {}", name, err);
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Runs before every simple command
+    pub fn run_debug() -> Result<(), Box<dyn std::error::Error>> {
+        Self::run("DEBUG")
+    }
+
+    /// Runs after a command fails where `set -e` would also apply
+    pub fn run_err() -> Result<(), Box<dyn std::error::Error>> {
+        Self::run("ERR")
+    }
+
+    /// Runs handlers for signals caught since the last safe point. A Ctrl-C
+    /// that killed the foreground command counts as SIGINT for the shell.
+    /// Returns false when that interrupt has no handler and is left for the
+    /// caller.
+    pub fn run_pending() -> Result<bool, Box<dyn std::error::Error>> {
+        let mut handled = true;
+        if JobControl::was_interrupted() {
+            match Self::get("INT") {
+                Some(_) => {
+                    JobControl::clear_interrupted();
+                    Self::run("INT")?;
+                }
+                None => handled = false,
+            }
+        }
+        for signal in Signals::take_pending() {
+            match Signals::name(signal) {
+                Some(name) if Self::get(name).is_some() => Self::run(name)?,
+                // only caught for the EXIT trap
+                Some(name) if ENDING_SIGNALS.contains(&name) => Self::die(signal),
+                _ => {}
+            }
+        }
+        Ok(handled)
+    }
+
+    /// Runs the EXIT trap for an ending signal nothing else traps, then
+    /// lets the signal end the shell as it would have without the trap
+    fn die(signal: i32) -> ! {
+        // a Ctrl-C that also killed the foreground command is this signal
+        JobControl::clear_interrupted();
+        Self::run_exit(128 + signal);
+        Signals::reset(signal);
+        Signals::raise(signal);
+        std::process::exit(128 + signal)
+    }
+
+    /// Runs the EXIT trap once, right before the shell or a script ends with
+    /// `code`. Returns the code to exit with, which an `exit` in the handler
+    /// may change.
+    pub fn run_exit(code: i32) -> i32 {
+        LAST_STATUS.store(code, Ordering::SeqCst);
+        // IN_TRAP is not checked: an `exit` inside another handler still
+        // runs the EXIT trap
+        let action = TRAPS.lock().unwrap().remove("EXIT");
+        let action = match action {
+            Some(action) if !action.is_empty() => action,
+            _ => return code,
+        };
+        match Interpreter::run_line(&action) {
+            Err(err) => match err.downcast_ref::<ErrExit>() {
+                Some(ErrExit(code)) => *code,
+                None => {
+                    print_error!("cotsh: trap EXIT: {}", err);
+                    code
+                }
+            },
+            Ok(_) => code,
+        }
+    }
+}
+
+pub struct TrapCommand;
+
+impl Builtin for TrapCommand {
+    fn name(&self) -> &'static str {
+        "trap"
+    }
+
+    fn help(&self) -> &'static str {
+        "trap [-lp] [[action] signal_spec ...]\nRun action when the shell receives a signal, or on EXIT, ERR or DEBUG."
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        &[
+            OptionSpec { flag: "-l", help: "list signal names" },
+            OptionSpec { flag: "-p", help: "print the traps that are set" },
+        ]
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        Ok(status(Self::trap(args), 1))
+    }
+}
+
+impl TrapCommand {
+    /// `trap [-lp] [[action] name...]`; an action of `-` resets the names,
+    /// an empty action ignores them
+    fn trap(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.unquote()).collect();
+        match args.first().map(String::as_str) {
+            None => Self::print(&[]),
+            Some("-p") => Self::print(&args[1..]),
+            Some("-l") => {
+                print_success!("{}", Signals::names().join(" "));
+                Ok(())
+            }
+            Some(_) if args.len() == 1 => Err("trap: usage: trap [-lp] [[action] signal_spec ...]".into()),
+            Some(action) => {
+                let mut res = Ok(());
+                for name in &args[1..] {
+                    match Traps::canonical(name) {
+                        Some(name) if action == "-" => Traps::reset(&name),
+                        Some(name) => Traps::set(name, action.to_string()),
+                        None => res = Err(format!("trap: {}: invalid signal specification", name).into()),
+                    }
+                }
+                res
+            }
+        }
+    }
+
+    fn print(names: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let traps = TRAPS.lock().unwrap().clone();
+        let names = match names.is_empty() {
+            true => traps.keys().cloned().collect(),
+            false => names.iter()
+                .map(|name| Traps::canonical(name).ok_or(format!("trap: {}: invalid signal specification", name)))
+                .collect::<Result<Vec<String>, String>>()?,
+        };
+        for name in names {
+            if let Some(action) = traps.get(&name) {
+                print_success!("trap -- '{}' {}", action.replace('\'', "'\\''"), name);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+use crate::commands::Aliases;
+use crate::commands::builtin::{status, Builtin, Context, OptionSpec};
+use crate::commands::executable_cmds::ExecutableCmds;
+use crate::commands::plugin::Plugin;
+use crate::utils::WordSplitter;
+use crate::{print_error, print_success, BUILTINS};
+
+/// One way a name can be run, in the order the shell tries them
+enum Found {
+    Alias(String),
+    Builtin,
+    Plugin(PathBuf),
+    File(PathBuf),
+}
+
+impl Found {
+    fn kind(&self) -> &'static str {
+        match self {
+            Found::Alias(_) => "alias",
+            Found::Builtin => "builtin",
+            Found::Plugin(_) => "plugin",
+            Found::File(_) => "file",
+        }
+    }
+}
+
+/// `type`, or `which` when `which` is set
+pub struct TypeCommand {
+    pub which: bool,
+}
+
+impl Builtin for TypeCommand {
+    fn name(&self) -> &'static str {
+        if self.which { "which" } else { "type" }
+    }
+
+    fn help(&self) -> &'static str {
+        match self.which {
+            true => "which [-a] name [name ...]\nPrint the path of each command name, or say it is an alias, builtin or plugin.",
+            false => "type [-apt] name [name ...]\nDescribe how each name would be run.",
+        }
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        const ALL: OptionSpec = OptionSpec { flag: "-a", help: "list every match, not just the one that runs" };
+        match self.which {
+            true => &[ALL],
+            false => &[
+                ALL,
+                OptionSpec { flag: "-p", help: "print only paths" },
+                OptionSpec { flag: "-t", help: "print alias, builtin, plugin or file" },
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+        match self.describe(args, &ctx.current_dir) {
+            Ok(status) => Ok(status),
+            Err(err) => Ok(status(Err(err), 2)),
+        }
+    }
+}
+
+impl TypeCommand {
+    /// `type [-apt] name...` and `which [-a] name...`. `-a` lists every
+    /// match instead of the one that would run, `-p` prints only paths and
+    /// `-t` a one-word kind. Fails when any name is not found.
+    fn describe(&self, args: &[String], current_dir: &Path) -> Result<i32, Box<dyn std::error::Error>> {
+        let which = self.which;
+        let name = self.name();
+        let (mut all, mut path_only, mut kind_only) = (false, which, false);
+        let mut names = args;
+        while let Some(flag) = names.first().map(|arg| arg.unquote()) {
+            if flag == "--" {
+                names = &names[1..];
+                break;
+            }
+            if !flag.starts_with('-') || flag == "-" {
+                break;
+            }
+            for char in flag.chars().skip(1) {
+                match char {
+                    'a' => all = true,
+                    'p' if !which => path_only = true,
+                    't' if !which => kind_only = true,
+                    _ => return Err(format!("{}: -{}: invalid option", name, char).into()),
+                }
+            }
+            names = &names[1..];
+        }
+        if names.is_empty() {
+            return Err(format!("{}: usage: {} [-a{}] name [name ...]", name, name, if which { "" } else { "pt" }).into());
+        }
+
+        let mut status = 0;
+        for arg in names {
+            let arg = arg.unquote();
+            let found = Self::lookup(&arg, all, current_dir);
+            if found.is_empty() {
+                if !kind_only {
+                    print_error!("cotsh: {}: {}: not found", name, arg);
+                }
+                status = 1;
+            }
+            for found in found {
+                match found {
+                    _ if kind_only => print_success!("{}", found.kind()),
+                    Found::File(path) if path_only => print_success!("{}", path.display()),
+                    Found::File(path) => print_success!("{} is {}", arg, path.display()),
+                    Found::Alias(value) if which => print_success!("{}: aliased to {}", arg, value),
+                    Found::Alias(_) if path_only => {}
+                    Found::Alias(value) => print_success!("{} is aliased to `{}'", arg, value),
+                    Found::Plugin(path) if which => print_success!("{}: plugin {}", arg, path.display()),
+                    Found::Plugin(path) if path_only => print_success!("{}", path.display()),
+                    Found::Plugin(path) => print_success!("{} is a plugin ({})", arg, path.display()),
+                    Found::Builtin if which => print_success!("{}: shell builtin", arg),
+                    Found::Builtin if path_only => {}
+                    Found::Builtin => print_success!("{} is a shell builtin", arg),
+                }
+            }
+        }
+        Ok(status)
+    }
+
+    /// What `name` would run as, or with `all` everything it could
+    fn lookup(name: &str, all: bool, current_dir: &Path) -> Vec<Found> {
+        let mut found = vec![];
+        if let Some(value) = Aliases::get(name) {
+            found.push(Found::Alias(value));
+            if !all {
+                return found;
+            }
+        }
+        if BUILTINS.get(name).is_some() {
+            found.push(Found::Builtin);
+            if !all {
+                return found;
+            }
+        }
+        if let Some(plugin) = Plugin::find(name) {
+            found.push(Found::Plugin(plugin.path().to_path_buf()));
+            if !all {
+                return found;
+            }
+        }
+        if all && !name.contains('/') {
+            found.extend(ExecutableCmds::find_all(name).into_iter().map(Found::File));
+        } else if let Ok(path) = ExecutableCmds::resolve(name, current_dir) {
+            found.push(Found::File(path));
+        }
+        found
+    }
+}
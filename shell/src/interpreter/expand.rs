@@ -0,0 +1,108 @@
+use std::env;
+use std::sync::atomic::Ordering;
+use crate::{LAST_STATUS, SHELL_OPTIONS};
+
+pub struct Expander;
+
+/// `set -u` and a variable that is not set, located at its `$` in the line
+#[derive(Debug)]
+pub struct UnboundVariable {
+    pub name: String,
+    pub column: usize,
+    pub len: usize,
+}
+
+impl std::fmt::Display for UnboundVariable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: unbound variable", self.name)
+    }
+}
+
+impl std::error::Error for UnboundVariable {}
+
+impl Expander {
+    /// Expands `$?`, `$$`, `$NAME` and `${NAME}` everywhere except inside
+    /// single quotes and after a backslash. Quotes are left in place for the
+    /// word splitter; unknown variables expand to an empty string, or are an
+    /// error under `set -u`.
+    pub fn expand_line(line: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut res = String::new();
+        let mut in_single = false;
+        let mut in_double = false;
+        let mut escaped = false;
+        let mut chars = line.chars().enumerate().peekable();
+
+        while let Some((column, ch)) = chars.next() {
+            if escaped {
+                res.push(ch);
+                escaped = false;
+                continue;
+            }
+            match ch {
+                '\\' if !in_single => {
+                    escaped = true;
+                    res.push(ch);
+                }
+                '\'' if !in_double => {
+                    in_single = !in_single;
+                    res.push(ch);
+                }
+                '"' if !in_single => {
+                    in_double = !in_double;
+                    res.push(ch);
+                }
+                '$' if !in_single => match chars.peek().map(|(_, c)| *c) {
+                    Some('?') => {
+                        chars.next();
+                        res.push_str(&LAST_STATUS.load(Ordering::SeqCst).to_string());
+                    }
+                    Some('$') => {
+                        chars.next();
+                        res.push_str(&std::process::id().to_string());
+                    }
+                    Some('{') => {
+                        chars.next();
+                        let mut name = String::new();
+                        for (_, inner) in chars.by_ref() {
+                            if inner == '}' {
+                                break;
+                            }
+                            name.push(inner);
+                        }
+                        let len = name.chars().count() + 3;
+                        res.push_str(&Self::lookup(&name, column, len)?);
+                    }
+                    Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+                        let mut name = String::new();
+                        while let Some(&(_, c)) = chars.peek() {
+                            if c != '_' && !c.is_ascii_alphanumeric() {
+                                break;
+                            }
+                            name.push(c);
+                            chars.next();
+                        }
+                        let len = name.len() + 1;
+                        res.push_str(&Self::lookup(&name, column, len)?);
+                    }
+                    _ => res.push(ch),
+                },
+                _ => res.push(ch),
+            }
+        }
+        Ok(res)
+    }
+
+    /// `column` and `len` place the reference in the line, for `set -u`
+    fn lookup(name: &str, column: usize, len: usize) -> Result<String, Box<dyn std::error::Error>> {
+        match name {
+            "?" => Ok(LAST_STATUS.load(Ordering::SeqCst).to_string()),
+            _ => match env::var(name) {
+                Ok(value) => Ok(value),
+                Err(_) if SHELL_OPTIONS.lock().unwrap().nounset => {
+                    Err(UnboundVariable { name: name.to_string(), column, len }.into())
+                }
+                Err(_) => Ok(String::new()),
+            },
+        }
+    }
+}
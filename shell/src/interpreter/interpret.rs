@@ -1,8 +1,67 @@
 use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
 use crate::commands::CmdParser;
-use crate::{print_error, print_success};
+use crate::interpreter::{Connector, ListItem, ListSplitter, SyntaxError, UnboundVariable};
+use crate::commands::Traps;
+use crate::utils::ResultPrinter;
+use crate::{LAST_STATUS, SHELL_OPTIONS};
+
+/// Raised when `set -e` is on and a command fails; carries its status
+#[derive(Debug)]
+pub struct ErrExit(pub i32);
+
+impl std::fmt::Display for ErrExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "exit status {}", self.0)
+    }
+}
+
+impl std::error::Error for ErrExit {}
+
+/// Raised when Ctrl-C killed a foreground command; ends the current list
+/// and any script running it
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// An error raised by one command of a list, located at that command
+#[derive(Debug)]
+pub struct CommandError {
+    pub column: usize,
+    pub len: usize,
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// The script line running now, so that errors its commands print are
+/// reported at it
+struct Source {
+    script_name: String,
+    line_number: usize,
+    line: String,
+    /// The command of the line that is running, and the length of its name
+    column: Option<(usize, usize)>,
+}
+
+static SOURCE: Mutex<Option<Source>> = Mutex::new(None);
 
 pub struct Interpreter{
+    script_name: Option<String>,
     script_lines: Vec<String>,
 }
 
@@ -13,42 +72,187 @@ impl Interpreter{
             false => return Err("File not found".into())
         };
 
+        // lines keep their indentation so reported columns match the file
         let script_lines = std::fs::read_to_string(&script_file)?
             .split('\n')
-            .map(|line| line.trim().to_string())
+            .map(|line| line.trim_end().to_string())
             .collect::<Vec<String>>();
-        Ok(Self{script_lines})
+        Ok(Self{script_name: Some(script_file.to_string_lossy().to_string()), script_lines})
     }
 
-    pub fn new_with_lines(script_lines: &String) -> Self{
-        let script_lines = script_lines.split(';')
-            .map(|line| line.trim().to_string())
-            .collect::<Vec<String>>();
-        Self{script_lines}
+    fn is_skippable(line: &str) -> bool {
+        let line = line.trim_start();
+        line.is_empty() || line.starts_with('#')
+    }
+
+    /// Makes `line_number` the source of the errors printed while it runs
+    /// and returns the source it replaces, which belongs to the script that
+    /// ran this one, if any
+    fn enter(&self, line_number: usize) -> Option<Source> {
+        let source = Source {
+            script_name: self.script_name.clone().unwrap_or_else(|| "cotsh".to_string()),
+            line_number,
+            line: self.script_lines.get(line_number - 1).cloned().unwrap_or_default(),
+            column: None,
+        };
+        SOURCE.lock().unwrap().replace(source)
+    }
+
+    /// Puts the location of the script line running now in front of an
+    /// error a command printed, in the `file:line:col: message` form of
+    /// `report`. Outside a script the message is left as it is.
+    pub fn located(message: &str) -> String {
+        match &*SOURCE.lock().unwrap() {
+            Some(source) => {
+                let message = message.strip_prefix("cotsh: ").unwrap_or(message);
+                Self::format_report(&source.script_name, source.line_number, &source.line, source.column, message)
+            }
+            None => message.to_string(),
+        }
     }
 
-    pub fn interpret(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut cmd_parser = CmdParser::default();
+    /// Runs the script and returns the status of the last command
+    pub fn interpret(&self) -> Result<i32, Box<dyn std::error::Error>> {
+        let mut status = 0;
 
         let mut counter: u32 = 1;
         for line in &self.script_lines{
-            cmd_parser = match  CmdParser::new(line){
-                Ok(cmd_parser) => {
-                    match cmd_parser.execute_cmd(){
-                        Ok(res) => res,
-                        Err(err) => {
-                            print_success!("Error on line {}: {}", counter, err);
-                            return Err(err);
-                        }   
-                    }
-                }
+            if Self::is_skippable(line) {
+                counter += 1;
+                continue;
+            }
+            let outer = self.enter(counter as usize);
+            let res = Self::run_line(line);
+            *SOURCE.lock().unwrap() = outer;
+            status = match res {
+                Ok(res) => res,
                 Err(err) => {
-                    print_error!("Error on line {}: {}", counter, err);
-                    return Err(err.into());
+                    if let Some(ErrExit(code)) = err.downcast_ref::<ErrExit>() {
+                        return Ok(*code);
+                    }
+                    if err.is::<Interrupted>() {
+                        return Err(err);
+                    }
+                    ResultPrinter::located_error(self.report(counter as usize, err.as_ref()));
+                    return Err(err);
                 }
             };
             counter += 1;
         }
+        Ok(status)
+    }
+
+    /// Runs a `;`, `&&` and `||` separated list, skipping commands whose
+    /// connector does not match the previous status. DEBUG, ERR and signal
+    /// traps run around each command. With `set -e`, a failing
+    /// command that is not tested by a following `&&`/`||` ends the list with
+    /// an `ErrExit` error. Other errors are wrapped in a `CommandError`.
+    pub fn run_line(line: &str) -> Result<i32, Box<dyn std::error::Error>> {
+        let mut status = LAST_STATUS.load(Ordering::SeqCst);
+        let list = ListSplitter::split(line)?;
+        for (index, item) in list.iter().enumerate() {
+            if !item.connector.should_run(status) {
+                continue;
+            }
+            // commands of a list inside an alias are not in the script line
+            if let Some(source) = SOURCE.lock().unwrap().as_mut().filter(|source| source.line == line) {
+                source.column = Some((item.column, Self::name_len(item)));
+            }
+            Traps::run_debug()?;
+            status = CmdParser::new(&item.command)
+                .and_then(|cmd_parser| cmd_parser.execute_cmd())
+                .map_err(|err| Self::locate(item, err))?;
+            // trap handlers run here, between commands, where it is safe
+            if !Traps::run_pending()? {
+                return Err(Interrupted.into());
+            }
+            let is_tested = list.get(index + 1)
+                .is_some_and(|next| matches!(next.connector, Connector::And | Connector::Or));
+            if status != 0 && !is_tested {
+                Traps::run_err()?;
+                if SHELL_OPTIONS.lock().unwrap().errexit {
+                    return Err(ErrExit(status).into());
+                }
+            }
+        }
+        Ok(status)
+    }
+
+    fn locate(item: &ListItem, err: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+        if err.is::<ErrExit>() || err.is::<Interrupted>() || err.is::<CommandError>() || err.is::<SyntaxError>() {
+            return err;
+        }
+        if let Some(unbound) = err.downcast_ref::<UnboundVariable>() {
+            let (column, len) = (item.column + unbound.column, unbound.len);
+            return Box::new(CommandError { column, len, source: err });
+        }
+        Box::new(CommandError { column: item.column, len: Self::name_len(item), source: err })
+    }
+
+    /// How many chars the command's name takes, for the caret under it
+    fn name_len(item: &ListItem) -> usize {
+        item.command.split_whitespace().next().map(|word| word.chars().count()).unwrap_or(1)
+    }
+
+    /// Formats an error as `file:line:col: message`, followed by the source
+    /// line and a caret underline when the error carries a column
+    fn report(&self, line_number: usize, err: &(dyn std::error::Error + 'static)) -> String {
+        let script_name = self.script_name.as_deref().unwrap_or("cotsh");
+        let at = if let Some(err) = err.downcast_ref::<SyntaxError>() {
+            Some((err.column, err.len))
+        } else {
+            err.downcast_ref::<CommandError>().map(|err| (err.column, err.len))
+        };
+        let source = self.script_lines.get(line_number - 1).map(String::as_str).unwrap_or_default();
+        Self::format_report(script_name, line_number, source, at, err)
+    }
+
+    fn format_report(
+        script_name: &str,
+        line_number: usize,
+        source: &str,
+        at: Option<(usize, usize)>,
+        message: impl std::fmt::Display,
+    ) -> String {
+        let (column, len) = match at {
+            Some(at) => at,
+            None => return format!("{}:{}: {}", script_name, line_number, message),
+        };
+        // keep tabs so the caret lines up with the source under any tab width
+        let padding: String = source.chars().take(column)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(line_number.to_string().len());
+        format!(
+            "{}:{}:{}: {}\n {} | {}\n {} | {}{}",
+            script_name, line_number, column + 1, message,
+            line_number, source,
+            gutter, padding, "^".repeat(len.max(1))
+        )
+    }
+
+    /// Runs every line of a startup file. Unlike `interpret`, a failing line is
+    /// reported and the remaining lines still run, so a broken rc file can
+    /// never keep the shell from starting. Ctrl+C skips the rest of the file;
+    /// `exit` and `set -e` failures come back as `ErrExit` to end the shell.
+    pub fn interpret_rc(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for (index, line) in self.script_lines.iter().enumerate() {
+            if Self::is_skippable(line) {
+                continue;
+            }
+            let outer = self.enter(index + 1);
+            let res = Self::run_line(line);
+            *SOURCE.lock().unwrap() = outer;
+            if let Err(err) = res {
+                if err.is::<ErrExit>() {
+                    return Err(err);
+                }
+                if err.is::<Interrupted>() {
+                    return Ok(());
+                }
+                ResultPrinter::located_error(self.report(index + 1, err.as_ref()));
+            }
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,9 @@
 mod tokenizer;
 mod interpret;
 mod parser;
+mod expand;
 
-pub use interpret::Interpreter;
-pub use parser::*;
\ No newline at end of file
+pub use interpret::{ErrExit, Interpreter, Interrupted};
+pub use parser::*;
+pub use expand::{Expander, UnboundVariable};
+pub use tokenizer::{Connector, ListItem, ListSplitter, SyntaxError};
@@ -0,0 +1,158 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    /// First command of a list, or the command after `;`
+    Always,
+    /// Command after `&&`, runs only when the previous status is 0
+    And,
+    /// Command after `||`, runs only when the previous status is non-zero
+    Or,
+}
+
+impl Connector {
+    pub fn should_run(&self, last_status: i32) -> bool {
+        match self {
+            Connector::Always => true,
+            Connector::And => last_status == 0,
+            Connector::Or => last_status != 0,
+        }
+    }
+}
+
+/// One command of a list and the 0-based char column it starts at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    pub connector: Connector,
+    pub command: String,
+    pub column: usize,
+}
+
+/// A line that cannot be split, located at the offending token
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub column: usize,
+    pub len: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Keywords that close compound commands cotsh does not parse; seeing one
+/// in command position is always a syntax error
+const UNEXPECTED_KEYWORDS: [&str; 7] = ["then", "else", "elif", "fi", "do", "done", "esac"];
+
+pub struct ListSplitter;
+
+impl ListSplitter {
+    /// Splits a line on `;`, `&&` and `||` that appear outside quotes.
+    /// A trailing `;` is allowed; any other empty command is a syntax error.
+    pub fn split(line: &str) -> Result<Vec<ListItem>, SyntaxError> {
+        let mut list = Vec::new();
+        let mut current = String::new();
+        let mut start = 0;
+        let mut connector = Connector::Always;
+        let mut quote: Option<(char, usize)> = None;
+        let mut escaped = false;
+        let mut chars = line.chars().enumerate().peekable();
+
+        while let Some((column, ch)) = chars.next() {
+            if escaped {
+                current.push(ch);
+                escaped = false;
+                continue;
+            }
+            let next_connector = match (ch, quote) {
+                ('\\', q) if q.map(|(q, _)| q) != Some('\'') => {
+                    escaped = true;
+                    current.push(ch);
+                    None
+                }
+                ('\'' | '"', None) => {
+                    quote = Some((ch, column));
+                    current.push(ch);
+                    None
+                }
+                (c, Some((q, _))) if c == q => {
+                    quote = None;
+                    current.push(ch);
+                    None
+                }
+                (';', None) => Some((Connector::Always, ";")),
+                ('&', None) if chars.peek().map(|(_, c)| *c) == Some('&') => {
+                    chars.next();
+                    Some((Connector::And, "&&"))
+                }
+                ('|', None) if chars.peek().map(|(_, c)| *c) == Some('|') => {
+                    chars.next();
+                    Some((Connector::Or, "||"))
+                }
+                _ => {
+                    current.push(ch);
+                    None
+                }
+            };
+            if let Some((next, token)) = next_connector {
+                if current.trim().is_empty() {
+                    return Err(SyntaxError {
+                        column,
+                        len: token.len(),
+                        message: format!("unexpected token '{}'", token),
+                    });
+                }
+                Self::push(&mut list, connector, &mut current, start)?;
+                connector = next;
+                start = column + token.len();
+            }
+        }
+        if let Some((q, column)) = quote {
+            return Err(SyntaxError {
+                column,
+                len: 1,
+                message: format!("unexpected end of line while looking for matching '{}'", q),
+            });
+        }
+        if current.trim().is_empty() && connector != Connector::Always {
+            return Err(SyntaxError {
+                column: line.chars().count(),
+                len: 1,
+                message: "unexpected end of line".to_string(),
+            });
+        }
+        Self::push(&mut list, connector, &mut current, start)?;
+        Ok(list)
+    }
+
+    /// Lines that fail to split count as lists so the error surfaces when run
+    pub fn is_list(line: &str) -> bool {
+        !matches!(Self::split(line), Ok(list) if list.len() <= 1)
+    }
+
+    fn push(
+        list: &mut Vec<ListItem>,
+        connector: Connector,
+        current: &mut String,
+        start: usize,
+    ) -> Result<(), SyntaxError> {
+        let command = current.trim().to_string();
+        let column = start + current.chars().take_while(|c| c.is_whitespace()).count();
+        current.clear();
+        if command.is_empty() {
+            return Ok(());
+        }
+        let first_word = command.split_whitespace().next().unwrap_or_default();
+        if UNEXPECTED_KEYWORDS.contains(&first_word) {
+            return Err(SyntaxError {
+                column,
+                len: first_word.chars().count(),
+                message: format!("unexpected token '{}'", first_word),
+            });
+        }
+        list.push(ListItem { connector, command, column });
+        Ok(())
+    }
+}
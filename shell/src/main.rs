@@ -1,32 +1,66 @@
 use crate::utils::DebugPrint;
+use std::collections::BTreeMap;
 use std::env::args;
+use std::io::{self, BufRead, IsTerminal};
 use std::path::PathBuf;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicI32, Ordering};
 use colored::{Color, Colorize};
 use figlet_rs::FIGfont;
 use once_cell::sync::Lazy;
-use crate::commands::CmdParser;
+use crate::commands::{CommandHash, CompSpec, ExecCommand, PluginCache, Registry, ShellOptions, Theme, Traps};
 use crate::commands::simple::ClearCommand;
-use crate::interpreter::Interpreter;
+use crate::interpreter::{ErrExit, Interpreter, Interrupted};
+use crate::startup::Startup;
 use crate::test::tester;
-use crate::utils::{Input};
+use crate::utils::{History, Input, JobControl, Tty};
 
 mod utils;
 mod commands;
 mod test;
 mod interpreter;
 mod var_map;
+mod startup;
 
 pub const DEBUG_MODE: Lazy<bool> = Lazy::new(|| args().any(|arg| arg == "--debug"));
-pub static CURRENT_DIR: Lazy<Mutex<PathBuf>> = Lazy::new(|| Mutex::new(PathBuf::from("/Users/cottons/Desktop")));
+pub static CURRENT_DIR: Lazy<Mutex<PathBuf>> = Lazy::new(|| {
+    Mutex::new(std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")))
+});
+/// Status of the most recently finished command, exposed as `$?`
+pub static LAST_STATUS: AtomicI32 = AtomicI32::new(0);
+pub static SHELL_OPTIONS: Lazy<Mutex<ShellOptions>> = Lazy::new(|| Mutex::new(ShellOptions::default()));
+pub static TRAPS: Lazy<Mutex<BTreeMap<String, String>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+/// Where `PATH` lookups found each command, see the `hash` builtin
+pub static COMMAND_HASH: Lazy<Mutex<CommandHash>> = Lazy::new(|| Mutex::new(CommandHash::default()));
+/// Installed plugins, read from the plugin directory once; `hash -r` reads it again
+pub static PLUGINS: Lazy<Mutex<PluginCache>> = Lazy::new(|| Mutex::new(PluginCache::default()));
+/// `alias` definitions, by name
+pub static ALIASES: Lazy<Mutex<BTreeMap<String, String>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+/// `complete` definitions, by command name
+pub static COMPLETIONS: Lazy<Mutex<BTreeMap<String, CompSpec>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+/// How the command line is coloured as it is typed, see the `highlight` builtin
+pub static THEME: Lazy<Mutex<Theme>> = Lazy::new(|| Mutex::new(Theme::default()));
+/// Every builtin, looked up by name when a command runs
+pub static BUILTINS: Lazy<Registry> = Lazy::new(Registry::default);
+/// Lines entered at the prompt, navigated with Up and Down
+pub static HISTORY: Lazy<Mutex<History>> = Lazy::new(|| Mutex::new(History::default()));
+pub static NO_RC: Lazy<bool> = Lazy::new(|| args().any(|arg| arg == "--norc"));
+/// `--login`/`-l`, or an argv[0] starting with `-` the way login(1) starts shells
+pub static LOGIN_SHELL: Lazy<bool> = Lazy::new(|| {
+    args().next().is_some_and(|arg0| arg0.starts_with('-'))
+        || args().skip(1).any(|arg| arg == "--login" || arg == "-l")
+});
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("args len : {} | args : {}", args().len(), args().collect::<Vec<_>>().join(" "));
+    dlog!("args len : {} | args : {}", args().len(), args().collect::<Vec<_>>().join(" "));
     if args().any(|arg| arg == "--test"){
         tester()?;
         return Ok(());
     }
     dlog!("Starting in debug mode");
+    if !io::stdin().is_terminal() {
+        stdin_mode();
+    }
     shell_mode()?;
     Ok(())
 }
@@ -71,18 +105,115 @@ fn greet(){
 
 
 fn shell_mode() -> Result<(), Box<dyn std::error::Error>> {
-    ClearCommand::run()?;
+    ExecCommand::reserve_fds();
+    Tty::save();
+    ClearCommand::clear()?;
     greet();
+    JobControl::init();
+    let startup = Startup::new(*LOGIN_SHELL, *NO_RC).run().err();
+    if let Some(ErrExit(code)) = startup.as_ref().and_then(|err| err.downcast_ref::<ErrExit>()) {
+        exit_shell(*code);
+    }
+    // after the startup files, which may set HISTSIZE
+    HISTORY.lock().unwrap().load();
     loop{
-        let input = {
-            let current_dir = CURRENT_DIR.lock().unwrap();
-            read_line!("{} -> ", &current_dir.to_string_lossy())
+        // signals that arrived while the shell sat at the prompt
+        let pending = Traps::run_pending().err();
+        if let Some(ErrExit(code)) = pending.as_ref().and_then(|err| err.downcast_ref::<ErrExit>()) {
+            exit_shell(*code);
+        }
+        // not held while reading: completion looks at the directory too
+        let current_dir = CURRENT_DIR.lock().unwrap().clone();
+        let input = match read_line!("{} -> ", &current_dir.to_string_lossy()) {
+            Ok(Some(input)) => input,
+            // Ctrl+D at an empty prompt
+            Ok(None) => {
+                print_success!("exit");
+                exit_shell(LAST_STATUS.load(Ordering::SeqCst));
+            }
+            // a trap handler run while reading
+            Err(err) => match err.downcast_ref::<ErrExit>() {
+                Some(ErrExit(code)) => exit_shell(*code),
+                None => continue,
+            },
         };
         if input.is_empty(){
             continue;
         }
-        CmdParser::new(&input)?.execute_cmd()?;
+        HISTORY.lock().unwrap().push(&input);
+        if let Some(code) = run_input(&input) {
+            exit_shell(code);
+        }
+        let mut history = HISTORY.lock().unwrap();
+        // suggestions leave out lines that failed
+        history.set_status(LAST_STATUS.load(Ordering::SeqCst));
+        if let Err(err) = history.append() {
+            print_error!("cotsh: history: {}", err);
+        }
+    }
+}
+
+/// Runs the lines piped or redirected into the shell, as in `echo ls | cotsh`
+/// or `cotsh < script`: no banner, no prompt, no startup files, no history
+/// and no job control, so commands stay in the shell's process group and an
+/// interrupt reaches them too. The shell exits with the status of the last
+/// command when the input ends.
+fn stdin_mode() -> ! {
+    ExecCommand::reserve_fds();
+    let mut line = Vec::new();
+    loop {
+        let pending = Traps::run_pending().err();
+        if let Some(ErrExit(code)) = pending.as_ref().and_then(|err| err.downcast_ref::<ErrExit>()) {
+            std::process::exit(Traps::run_exit(*code));
+        }
+        line.clear();
+        // the lock is dropped before the line runs, so builtins can read stdin too
+        match io::stdin().lock().read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                print_error!("cotsh: stdin: {}", err);
+                LAST_STATUS.store(1, Ordering::SeqCst);
+                break;
+            }
+        }
+        let input = String::from_utf8_lossy(&line);
+        let input = input.trim_end_matches(['\n', '\r']);
+        if input.trim().is_empty() || input.trim_start().starts_with('#') {
+            continue;
+        }
+        if let Some(code) = run_input(input) {
+            std::process::exit(Traps::run_exit(code));
+        }
+    }
+    std::process::exit(Traps::run_exit(LAST_STATUS.load(Ordering::SeqCst)));
+}
+
+/// Runs a line and leaves its status in `$?`. Returns the exit code when the
+/// line ends the shell, through `exit` or `set -e`.
+fn run_input(input: &str) -> Option<i32> {
+    JobControl::clear_interrupted();
+    if let Err(err) = Interpreter::run_line(input) {
+        if let Some(ErrExit(code)) = err.downcast_ref::<ErrExit>() {
+            return Some(*code);
+        }
+        if err.is::<Interrupted>() {
+            LAST_STATUS.store(130, Ordering::SeqCst);
+        } else {
+            print_error!("cotsh: {}", err);
+            LAST_STATUS.store(1, Ordering::SeqCst);
+        }
+    }
+    None
+}
+
+/// Runs the EXIT trap and saves the history before the interactive shell ends
+fn exit_shell(code: i32) -> ! {
+    let code = Traps::run_exit(code);
+    if let Err(err) = HISTORY.lock().unwrap().save() {
+        print_error!("cotsh: history: {}", err);
     }
+    std::process::exit(code);
 }
 
 fn interpret_mode(script_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
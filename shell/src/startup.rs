@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+use crate::interpreter::Interpreter;
+use crate::utils::Dirs;
+use crate::print_error;
+
+/// Decides which startup files an interactive shell sources and runs them.
+pub struct Startup {
+    login: bool,
+    norc: bool,
+}
+
+impl Startup {
+    pub fn new(login: bool, norc: bool) -> Self {
+        Self { login, norc }
+    }
+
+    /// Startup files in the order they are sourced. Login shells read the
+    /// profile files first; the rc file is read by every interactive shell,
+    /// preferring the XDG location over `~/.cotshrc`.
+    pub fn rc_files(&self, home: Option<PathBuf>, config_dir: Option<PathBuf>) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        if self.norc {
+            return files;
+        }
+        if self.login {
+            files.push(PathBuf::from("/etc/profile"));
+            if let Some(home) = &home {
+                files.push(home.join(".profile"));
+            }
+        }
+        let xdg_rc = config_dir.map(|dir| dir.join("cotshrc"));
+        match xdg_rc {
+            Some(rc) if rc.is_file() => files.push(rc),
+            _ => {
+                if let Some(home) = &home {
+                    files.push(home.join(".cotshrc"));
+                }
+            }
+        }
+        files
+    }
+
+    /// Sources the startup files. Stops at an `exit`, or a failure under
+    /// `set -e`, and returns its `ErrExit`.
+    pub fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for rc_file in self.rc_files(Dirs::home(), Dirs::config_dir()) {
+            if !rc_file.is_file() {
+                continue;
+            }
+            match Interpreter::new(rc_file.clone()) {
+                Ok(interpreter) => interpreter.interpret_rc()?,
+                Err(err) => print_error!("cotsh: {}: {}", rc_file.display(), err),
+            }
+        }
+        Ok(())
+    }
+}
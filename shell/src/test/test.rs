@@ -9,10 +9,105 @@ use crate::dlog;
 use crate::interpreter::{eval, tokenize, Parser};
 use crate::test::table_printer::tabel_tester_2;
 
+fn greet(font: &str){
+    // Load thicc block font from file
+    let font = FIGfont::from_file("figlet-fonts/Sub-Zero.flf")
+        .unwrap_or_else(|_| FIGfont::standard().unwrap());
+    let figure = font.convert("COTSH").unwrap();
+
+    // Rainbow gradient 🌈
+    let gradient = [
+        Color::Green,
+        Color::Yellow,
+        Color::Red,
+        Color::Magenta,
+        Color::Blue,
+    ];
+
+    let ascii = figure.to_string(); // owns the data
+    let lines: Vec<&str> = ascii.lines().collect(); // borrow from owned string
+    let total = lines.len();
+
+    for (i, line) in lines.iter().enumerate() {
+        let color_index = i * gradient.len() / total;
+        println!("{}", line.color(gradient[color_index]));
+    }
+}
+
+
+
+pub fn tester() -> Result<(), Box<dyn std::error::Error>> {
+
+    let font_path = PathBuf::from("/Users/cottons/Documents/rust_shell/figlet-fonts");
+
+    let fonts = match font_path.read_dir(){
+        Ok(fonts) => fonts,
+        Err(e) => {
+            eprintln!("Error reading fonts: {}", e);
+            return Ok(())
+        }
+    };
+
+    for font in fonts {
+        match font {
+            Ok(font) => {
+                if font.file_name().to_str().unwrap_or_else(Default::default).contains(".flf"){
+                    println!("font: {}", font.file_name().to_str().unwrap());
+                    greet(font.file_name().to_str().unwrap());
+                }
+
+            },
+            Err(e) => {
+                eprintln!("Error reading font: {}", e);
+            }
+        }
+    }
+
+
+    Ok(())
+}
+
 #[allow(dead_code)]
 #[cfg(test)]
 mod tests {
-    use crate::utils::WordSplitter;
+    use std::path::PathBuf;
+    use crate::commands::{Aliases, Completion, Highlighter};
+    use crate::interpreter::{Expander, ListSplitter};
+    use crate::startup::Startup;
+    use crate::utils::{DisplayWidth, History, LineBuffer, Signals, WordSplitter};
+
+    /// Runs the builtin `name` and returns its status
+    fn run_builtin(name: &str, args: &[&str]) -> i32 {
+        use crate::commands::builtin::Context;
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        crate::BUILTINS.get(name).unwrap().run(&mut Context::new(), &args).unwrap()
+    }
+
+    /// A directory for one test, named after it and the process so parallel
+    /// runs don't share it, and removed with its contents when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("cotsh-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = PathBuf;
+
+        fn deref(&self) -> &PathBuf {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
 
     #[test]
     fn single_quote_with_escaped_quote() {
@@ -74,62 +169,597 @@ mod tests {
         let splitter = temp.advance_split();
         assert_eq!(splitter, vec!["'test\\'s test'", "test2"])
     }
-}
 
-fn greet(font: &str){
-    // Load thicc block font from file
-    let font = FIGfont::from_file("figlet-fonts/Sub-Zero.flf")
-        .unwrap_or_else(|_| FIGfont::standard().unwrap());
-    let figure = font.convert("COTSH").unwrap();
+    #[test]
+    fn rc_files_login_order() {
+        let home = PathBuf::from("/nonexistent/home");
+        let files = Startup::new(true, false).rc_files(Some(home.clone()), Some(home.join(".config/cotsh")));
+        assert_eq!(files, vec![
+            PathBuf::from("/etc/profile"),
+            home.join(".profile"),
+            home.join(".cotshrc"),
+        ]);
+    }
 
-    // Rainbow gradient 🌈
-    let gradient = [
-        Color::Green,
-        Color::Yellow,
-        Color::Red,
-        Color::Magenta,
-        Color::Blue,
-    ];
+    #[test]
+    fn list_split_respects_quotes() {
+        let list = ListSplitter::split("echo 'a;b' && false || echo \"c&&d\"; ls").unwrap();
+        let commands: Vec<&str> = list.iter().map(|item| item.command.as_str()).collect();
+        assert_eq!(commands, vec!["echo 'a;b'", "false", "echo \"c&&d\"", "ls"]);
+        let columns: Vec<usize> = list.iter().map(|item| item.column).collect();
+        assert_eq!(columns, vec![0, 14, 23, 36]);
+        // after a failure only `||` and `;` commands run
+        let runs: Vec<bool> = list.iter().map(|item| item.connector.should_run(1)).collect();
+        assert_eq!(runs, vec![true, false, true, true]);
+    }
 
-    let ascii = figure.to_string(); // owns the data
-    let lines: Vec<&str> = ascii.lines().collect(); // borrow from owned string
-    let total = lines.len();
+    #[test]
+    fn list_split_syntax_errors() {
+        let err = ListSplitter::split("echo a; fi").unwrap_err();
+        assert_eq!((err.column, err.len, err.message.as_str()), (8, 2, "unexpected token 'fi'"));
+        let err = ListSplitter::split("ls && && pwd").unwrap_err();
+        assert_eq!((err.column, err.len), (6, 2));
+        let err = ListSplitter::split("echo \"open").unwrap_err();
+        assert_eq!(err.column, 5);
+        assert!(ListSplitter::split("ls ||").is_err());
+        assert!(ListSplitter::split("ls;").is_ok());
+    }
 
-    for (i, line) in lines.iter().enumerate() {
-        let color_index = i * gradient.len() / total;
-        println!("{}", line.color(gradient[color_index]));
+    #[test]
+    fn expand_skips_single_quotes() {
+        let line = Expander::expand_line("echo '$?' \"$?\" \\$?").unwrap();
+        assert!(line.starts_with("echo '$?' \""));
+        assert!(line.ends_with("\\$?"));
+        assert!(!line.contains("\"$?\""));
     }
-}
 
+    #[test]
+    fn unquote_words() {
+        assert_eq!("'rm -f $tmp; exit'".to_string().unquote(), "rm -f $tmp; exit");
+        assert_eq!("\"a \\\"b\\\" \\n\"".to_string().unquote(), "a \"b\" \\n");
+        assert_eq!("it\\'s".to_string().unquote(), "it's");
+        assert_eq!("name='value'".to_string().unquote(), "name=value");
+    }
 
+    #[test]
+    fn signal_names() {
+        assert_eq!(Signals::number("INT"), Signals::number("sigint"));
+        assert_eq!(Signals::number("2"), Some(2));
+        assert_eq!(Signals::number("EXIT"), None);
+        assert_eq!(Signals::name(15), Some("TERM"));
+    }
 
-pub fn tester() -> Result<(), Box<dyn std::error::Error>> {
+    #[test]
+    fn alias_expansion() {
+        Aliases::set("ll", "ls -l");
+        Aliases::set("ls", "ls -F");
+        Aliases::set("sudo", "sudo ");
+        Aliases::set("loop1", "loop2 x");
+        Aliases::set("loop2", "loop1 y");
+        assert_eq!(Aliases::expand("ll -a").0, "ls -F -l -a");
+        assert_eq!(Aliases::expand("sudo ll").0, "sudo  ls -F -l");
+        assert_eq!(Aliases::expand("echo ll").0, "echo ll");
+        assert_eq!(Aliases::expand("'ll'").0, "'ll'");
+        let (text, used) = Aliases::expand("loop1");
+        assert_eq!(text, "loop1 y x");
+        assert_eq!(used, ["loop1", "loop2"]);
+    }
 
-    let font_path = PathBuf::from("/Users/cottons/Documents/rust_shell/figlet-fonts");
+    #[test]
+    fn history_skips_blank_lines() {
+        let mut history = History::default();
+        history.push("ls -l");
+        history.push("   ");
+        history.push("cd /tmp");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(1).map(String::as_str), Some("cd /tmp"));
+    }
 
-    let fonts = match font_path.read_dir(){
-        Ok(fonts) => fonts,
-        Err(e) => {
-            eprintln!("Error reading fonts: {}", e);
-            return Ok(())
+    #[test]
+    fn complete_escapes_paths() {
+        let dir = TempDir::new("complete");
+        std::fs::create_dir_all(dir.join("My Documents")).unwrap();
+        let line = format!("ls {}/My\\ D", dir.display());
+        let completion = Completion::new(&line, line.len());
+        assert_eq!(completion.replacement(), Some(format!("{}/My\\ Documents/", dir.display())));
+        let line = format!("ls '{}/My D", dir.display());
+        let completion = Completion::new(&line, line.len());
+        assert_eq!(completion.replacement(), Some(format!("'{}/My Documents/", dir.display())));
+    }
+
+    #[test]
+    fn line_buffer_kill_yank_undo() {
+        let mut line = LineBuffer::new();
+        "echo one two".chars().for_each(|c| line.insert(c));
+        line.kill_blank_word();
+        line.kill_blank_word();
+        assert_eq!(line.text(), "echo ");
+        line.yank();
+        assert_eq!(line.text(), "echo one two");
+        line.word_left();
+        line.transpose();
+        assert_eq!(line.text(), "echo onet wo");
+        line.undo();
+        line.undo();
+        assert_eq!(line.text(), "echo ");
+    }
+
+    #[test]
+    fn line_buffer_graphemes_and_width() {
+        let mut line = LineBuffer::new();
+        "caf\u{65}\u{301}\u{1F642}".chars().for_each(|c| line.insert(c));
+        line.move_left();
+        line.backspace();
+        assert_eq!(line.text(), "caf\u{1F642}");
+        assert_eq!(line.text().display_width(), 5);
+        assert_eq!("\x1b[1;32m日本 -> \x1b[0m".display_width(), 8);
+    }
+
+    #[test]
+    fn highlight_roles() {
+        use crossterm::style::{Attribute, Color};
+        let line = "ls 'a b' $HOME 2>&1 | nosuchcmd # note";
+        let spans = Highlighter::new().spans(line, line.len(), "");
+        let color = |text: &str| spans.iter().find(|(span, _)| *span == text).and_then(|(_, style)| style.foreground_color);
+        assert_eq!(color("ls"), Some(Color::Green));
+        assert_eq!(color("'a b'"), Some(Color::Yellow));
+        assert_eq!(color("$HOME"), Some(Color::Cyan));
+        assert_eq!(color("2>&1"), Some(Color::Blue));
+        assert_eq!(color("nosuchcmd"), Some(Color::Red));
+        assert_eq!(color("# note"), Some(Color::DarkGrey));
+
+        let spans = Highlighter::new().spans("echo (x)", 5, "");
+        let matched: Vec<&str> = spans.iter().filter(|(_, style)| style.attributes.has(Attribute::Reverse)).map(|(span, _)| *span).collect();
+        assert_eq!(matched, ["(", ")"]);
+    }
+
+    #[test]
+    fn rc_files_norc() {
+        let files = Startup::new(true, true).rc_files(Some(PathBuf::from("/home")), None);
+        assert!(files.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn job_control_keeps_children_in_shell_group() {
+        use std::process::Command;
+        use crate::utils::JobControl;
+        // tests never call `init`, so this is a non-interactive shell
+        let mut command = Command::new("sleep");
+        command.arg("5");
+        JobControl::prepare(&mut command);
+        let child = command.spawn().unwrap();
+        let pid = child.id() as libc::pid_t;
+        // SAFETY: plain syscalls on our own child
+        unsafe {
+            assert_eq!(libc::getpgid(pid), libc::getpgrp());
+            libc::kill(pid, libc::SIGTERM);
         }
-    };
+        assert_eq!(JobControl::wait(child).unwrap(), 128 + libc::SIGTERM);
+    }
 
-    for font in fonts {
-        match font {
-            Ok(font) => {
-                if font.file_name().to_str().unwrap_or_else(Default::default).contains(".flf"){
-                    println!("font: {}", font.file_name().to_str().unwrap());
-                    greet(font.file_name().to_str().unwrap());
+    #[test]
+    fn history_suggestions() {
+        use std::path::Path;
+        let mut history = History::default();
+        history.read("#0 /src\ngit status\n#0 /tmp\ngit stash\n#1 /src\ngit stash pop\n\\#1 /tmp note\n#0 /tmp\ngit log\n");
+        assert_eq!(history.len(), 5);
+        // a typed comment that looks like an entry's status and directory
+        assert_eq!(history.get(3).map(String::as_str), Some("#1 /tmp note"));
+        // the failed `git stash pop` is skipped, and `/src` prefers its own entry
+        assert_eq!(history.suggest("git st", Path::new("/src")), Some("atus"));
+        assert_eq!(history.suggest("git st", Path::new("/tmp")), Some("ash"));
+        assert_eq!(history.suggest("git st", Path::new("/home")), Some("ash"));
+        assert_eq!(history.suggest("git log", Path::new("/tmp")), None);
+    }
+
+    #[test]
+    fn vi_mode_commands() {
+        use crossterm::event::{KeyCode, KeyModifiers};
+        use crate::utils::{ViAction, ViMode};
+        // keys as read_line hands them over, inserting what vi mode passes back
+        let press = |vi: &mut ViMode, line: &mut LineBuffer, keys: &str| {
+            for key in keys.chars() {
+                let code = if key == '\x1b' { KeyCode::Esc } else { KeyCode::Char(key) };
+                if let ViAction::Key(KeyCode::Char(char), _) = vi.key(code, KeyModifiers::NONE, line) {
+                    line.insert(char);
                 }
+            }
+        };
+        let mut vi = ViMode::new();
+        let mut line = LineBuffer::new();
+        press(&mut vi, &mut line, "one two three four five\x1b");
+        assert_eq!(line.cursor(), 22);
+        press(&mut vi, &mut line, "0w");
+        assert_eq!(line.cursor(), 4);
+        press(&mut vi, &mut line, "2e");
+        assert_eq!(line.cursor(), 12);
+        press(&mut vi, &mut line, "Fo");
+        assert_eq!(line.cursor(), 6);
 
-            },
-            Err(e) => {
-                eprintln!("Error reading font: {}", e);
+        press(&mut vi, &mut line, "0dw");
+        assert_eq!(line.text(), "two three four five");
+        press(&mut vi, &mut line, ".");
+        assert_eq!(line.text(), "three four five");
+        press(&mut vi, &mut line, "2.");
+        assert_eq!(line.text(), "five");
+        press(&mut vi, &mut line, "u");
+        assert_eq!(line.text(), "three four five");
+        press(&mut vi, &mut line, "2d2w");
+        assert_eq!(line.text(), "");
+        press(&mut vi, &mut line, "u");
+        assert_eq!(line.text(), "three four five");
+
+        // a change and the text typed for it repeat together
+        press(&mut vi, &mut line, "0cwTHREE\x1bw.");
+        assert_eq!(line.text(), "THREE THREE five");
+
+        // counts too large for a usize
+        press(&mut vi, &mut line, "0w9999999999d9999999999l");
+        assert_eq!(line.text(), "THREE ");
+        press(&mut vi, &mut line, "0");
+        press(&mut vi, &mut line, "18446744073709551615rx99999999999999999999999999rx");
+        assert_eq!(line.text(), "THREE ");
+        press(&mut vi, &mut line, "3rx");
+        assert_eq!(line.text(), "xxxEE ");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn files_without_execute_bit_or_shebang() {
+        use std::os::unix::fs::PermissionsExt;
+        use crate::commands::executable_cmds::{ExecOutcome, ExecutableCmds};
+        let dir = TempDir::new("exec");
+        let write = |name: &str, text: &str, mode: u32| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        write("noexec", "#!/bin/sh\nexit 3\n", 0o644);
+        write("plain", "echo hi\n", 0o755);
+        write("shebang", "#!/bin/sh\nexit 3\n", 0o755);
+
+        assert_eq!(ExecutableCmds::resolve("./noexec", &dir).unwrap_err().status(), 126);
+        assert_eq!(ExecutableCmds::resolve("./missing", &dir).unwrap_err().status(), 127);
+        // a directory is found but can't be run
+        assert_eq!(ExecutableCmds::resolve("./", &dir).unwrap_err().status(), 126);
+
+        let run = |name: &str| {
+            let cmd = ExecutableCmds::new(name, &vec![], &dir).unwrap();
+            cmd.execute_cmd().unwrap()
+        };
+        assert!(matches!(run("./plain"), ExecOutcome::Script(path) if path.ends_with("plain")));
+        assert!(matches!(run("./shebang"), ExecOutcome::Status(3)));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn hash_table_entries() {
+        use std::os::unix::fs::PermissionsExt;
+        use crate::commands::CommandHash;
+        let dir = TempDir::new("hash");
+        let tool = dir.join("tool");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        let chmod = |mode: u32| std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(mode)).unwrap();
+        chmod(0o755);
+
+        // an entry is dropped once its file is no longer executable, or gone
+        let mut table = CommandHash::default();
+        table.insert("tool", tool.clone(), 0);
+        assert_eq!(table.lookup("tool"), Some(tool.clone()));
+        chmod(0o644);
+        assert_eq!(table.lookup("tool"), None);
+        chmod(0o755);
+        assert_eq!(table.lookup("tool"), None);
+        table.insert("tool", tool.clone(), 0);
+        std::fs::remove_file(&tool).unwrap();
+        assert_eq!(table.lookup("tool"), None);
+
+        assert_eq!(run_builtin("hash", &["-p", "/bin/sh", "cotsh-hash-test"]), 0);
+        assert_eq!(run_builtin("hash", &["-t", "cotsh-hash-test"]), 0);
+        assert_eq!(run_builtin("hash", &["-d", "cotsh-hash-test"]), 0);
+        assert_eq!(run_builtin("hash", &["-d", "cotsh-hash-test"]), 1);
+        assert_eq!(run_builtin("hash", &["-t", "cotsh-hash-test"]), 1);
+        assert_eq!(run_builtin("hash", &["-p", "/bin/sh"]), 1);
+        assert_eq!(run_builtin("hash", &["cotsh-no-such-command"]), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn exec_redirects_the_shell() {
+        use std::io::Read;
+        use std::os::fd::FromRawFd;
+        let dir = TempDir::new("exec-fds");
+        let log = dir.join("log").display().to_string();
+        // descriptors far above anything the other tests open
+        let write = |fd: i32, text: &str| {
+            // SAFETY: writing to a descriptor the test owns
+            unsafe { libc::write(fd, text.as_ptr().cast(), text.len()) }
+        };
+        assert_eq!(run_builtin("exec", &["200>", &log]), 0);
+        write(200, "one\n");
+        assert_eq!(run_builtin("exec", &["201>&200"]), 0);
+        write(201, "two\n");
+        assert_eq!(run_builtin("exec", &["200>&-", "201>&-"]), 0);
+        assert_eq!(write(200, "gone\n"), -1);
+        assert_eq!(run_builtin("exec", &[&format!("200>>{}", log)]), 0);
+        write(200, "three\n");
+        assert_eq!(run_builtin("exec", &["200>&-"]), 0);
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "one\ntwo\nthree\n");
+
+        assert_eq!(run_builtin("exec", &[&format!("200<{}", log)]), 0);
+        let mut text = String::new();
+        // SAFETY: takes over fd 200, which the shell opened above
+        unsafe { std::fs::File::from_raw_fd(200) }.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "one\ntwo\nthree\n");
+
+        assert_eq!(run_builtin("exec", &[&format!("200<{}", dir.join("missing").display())]), 1);
+        assert_eq!(run_builtin("exec", &["200>&201"]), 1);
+        assert_eq!(run_builtin("exec", &["200>&x"]), 1);
+        assert_eq!(run_builtin("exec", &["200>"]), 1);
+    }
+
+    #[test]
+    fn command_and_builtin_statuses() {
+        assert_eq!(run_builtin("command", &["-v", "echo"]), 0);
+        assert_eq!(run_builtin("command", &["-V", "echo"]), 0);
+        assert_eq!(run_builtin("command", &["-v", "cotsh-no-such-command"]), 1);
+        assert_eq!(run_builtin("command", &["-V", "cotsh-no-such-command"]), 1);
+        assert_eq!(run_builtin("command", &["-x", "echo"]), 2);
+        assert_eq!(run_builtin("command", &[]), 0);
+        // the command runs and its status comes back, found on the default PATH with -p
+        assert_eq!(run_builtin("command", &["sh", "-c", "exit 3"]), 3);
+        assert_eq!(run_builtin("command", &["-p", "sh", "-c", "exit 4"]), 4);
+        assert_eq!(run_builtin("command", &["cotsh-no-such-command"]), 127);
+
+        assert_eq!(run_builtin("builtin", &["command", "-v", "echo"]), 0);
+        assert_eq!(run_builtin("builtin", &["sh", "-c", "exit 3"]), 1);
+        assert_eq!(run_builtin("builtin", &[]), 0);
+    }
+
+    #[test]
+    fn type_and_which_statuses() {
+        assert_eq!(run_builtin("type", &["echo", "sh"]), 0);
+        assert_eq!(run_builtin("type", &["-a", "echo"]), 0);
+        assert_eq!(run_builtin("type", &["-pt", "sh"]), 0);
+        // any name not found fails, even quietly with -t
+        assert_eq!(run_builtin("type", &["echo", "cotsh-no-such-command"]), 1);
+        assert_eq!(run_builtin("type", &["-t", "cotsh-no-such-command"]), 1);
+        assert_eq!(run_builtin("type", &["-a", "cotsh-no-such-command"]), 1);
+        // no names and unknown flags are usage errors
+        assert_eq!(run_builtin("type", &[]), 2);
+        assert_eq!(run_builtin("type", &["-a"]), 2);
+        assert_eq!(run_builtin("type", &["-x", "echo"]), 2);
+        assert_eq!(run_builtin("type", &["--", "-a"]), 1);
+
+        assert_eq!(run_builtin("which", &["-a", "sh"]), 0);
+        assert_eq!(run_builtin("which", &[]), 2);
+        assert_eq!(run_builtin("which", &["-t", "sh"]), 2);
+    }
+
+    #[test]
+    fn builtin_registry_lookup() {
+        use crate::commands::builtin::{Builtin, Context};
+        use crate::commands::Registry;
+        struct Answer;
+        impl Builtin for Answer {
+            fn name(&self) -> &'static str {
+                "answer"
+            }
+            fn help(&self) -> &'static str {
+                "answer\nReturn 42."
+            }
+            fn run(&self, _ctx: &mut Context, args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+                Ok(42 + args.len() as i32)
             }
         }
+
+        let registry = Registry::default();
+        for name in ["cd", "echo", "exit", "hash", "pwd", "type", "which"] {
+            assert_eq!(registry.get(name).map(|builtin| builtin.name()), Some(name));
+        }
+        assert!(registry.get("answer").is_none());
+        assert!(registry.get("ECHO").is_none());
+        assert!(registry.get("").is_none());
+        // listed by name, each usage line starting with it
+        let names: Vec<&str> = registry.iter().map(|builtin| builtin.name()).collect();
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(registry.iter().all(|builtin| builtin.help().starts_with(builtin.name())));
+
+        let mut registry = Registry::new();
+        assert_eq!(registry.iter().count(), 0);
+        registry.register(Box::new(Answer));
+        let answer = registry.get("answer").unwrap();
+        assert_eq!(answer.run(&mut Context::new(), &["x".to_string()]).unwrap(), 43);
     }
 
+    #[test]
+    fn history_search_steps() {
+        let mut history = History::default();
+        history.read("make test\ncargo build\nmake\ncargo test\ncargo build\nls\n");
+        // typing searches back from the newest entry
+        assert_eq!(history.search("build", Some(history.len() - 1), true, None), Some((4, 6)));
+        assert_eq!(history.search("nothing", Some(history.len() - 1), true, None), None);
 
-    Ok(())
-}
\ No newline at end of file
+        // Ctrl-R steps to older matches, skipping repeats of the current line
+        assert_eq!(history.search_next("cargo", None, true), Some((4, 0)));
+        assert_eq!(history.search_next("cargo", Some(4), true), Some((3, 0)));
+        assert_eq!(history.search_next("cargo", Some(3), true), Some((1, 0)));
+        // the older `cargo build` is the line already shown
+        assert_eq!(history.search_next("build", Some(4), true), None);
+        assert_eq!(history.search_next("make", Some(2), true), Some((0, 0)));
+        assert_eq!(history.search_next("make", Some(0), true), None);
+
+        // Ctrl-S steps back towards newer ones
+        assert_eq!(history.search_next("test", Some(0), false), Some((3, 6)));
+        assert_eq!(history.search_next("cargo", Some(1), false), Some((3, 0)));
+        assert_eq!(history.search_next("test", None, false), Some((0, 5)));
+        assert_eq!(history.search_next("test", Some(3), false), None);
+
+        assert_eq!(History::default().search_next("make", None, true), None);
+    }
+
+    #[test]
+    fn complete_options() {
+        use crate::commands::CompSpec;
+        let dir = TempDir::new("complete-o");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sfile"), "").unwrap();
+        let replace = |line: String| Completion::new(&line, line.len()).replacement();
+
+        assert_eq!(run_builtin("complete", &["-a", "alpha", "cotsh-t-words"]), 0);
+        assert_eq!(replace("cotsh-t-words al".into()), Some("alpha ".into()));
+        // only the words: no paths without -o
+        assert_eq!(replace(format!("cotsh-t-words {}/s", dir.display())), None);
+
+        assert_eq!(run_builtin("complete", &["-o", "nospace", "-a", "--color= --colour", "cotsh-t-nospace"]), 0);
+        assert_eq!(replace("cotsh-t-nospace --colo".into()), None);
+        assert_eq!(replace("cotsh-t-nospace --color".into()), Some("--color=".into()));
+
+        assert_eq!(run_builtin("complete", &["-o", "default", "-a", "alpha", "cotsh-t-default"]), 0);
+        assert_eq!(replace(format!("cotsh-t-default {}/sf", dir.display())), Some(format!("{}/sfile ", dir.display())));
+        assert_eq!(run_builtin("complete", &["-o", "dirnames", "cotsh-t-dirnames"]), 0);
+        assert_eq!(replace(format!("cotsh-t-dirnames {}/s", dir.display())), Some(format!("{}/sub/", dir.display())));
+        assert_eq!(run_builtin("complete", &["-o", "plusdirs", "-a", "alpha", "cotsh-t-plusdirs"]), 0);
+        let line = format!("cotsh-t-plusdirs {}/", dir.display());
+        assert_eq!(Completion::new(&line, line.len()).len(), 1);
+
+        let spec = CompSpec::get("cotsh-t-plusdirs").unwrap();
+        assert_eq!(spec.words, vec!["alpha"]);
+        assert!(spec.has_option("plusdirs") && !spec.has_option("nospace"));
+
+        assert_eq!(run_builtin("complete", &["-o", "bogus", "cotsh-t-bad"]), 2);
+        assert_eq!(run_builtin("complete", &["-a"]), 2);
+        assert_eq!(run_builtin("complete", &["-x", "cotsh-t-bad"]), 2);
+        assert!(CompSpec::get("cotsh-t-bad").is_none());
+
+        assert_eq!(run_builtin("complete", &["-p", "cotsh-t-words"]), 0);
+        assert_eq!(run_builtin("complete", &["-p", "cotsh-t-missing"]), 1);
+        for name in ["cotsh-t-words", "cotsh-t-nospace", "cotsh-t-default", "cotsh-t-dirnames", "cotsh-t-plusdirs"] {
+            assert_eq!(run_builtin("complete", &["-r", name]), 0);
+            assert!(CompSpec::get(name).is_none());
+        }
+        assert_eq!(run_builtin("complete", &["-r", "cotsh-t-words"]), 1);
+    }
+
+    #[test]
+    fn renderer_wraps_at_the_edge() {
+        use crate::utils::Renderer;
+        // "$ " takes two of the ten columns
+        let renderer = Renderer::with_width("$ ".to_string(), 10);
+        assert_eq!(renderer.layout("1234567", 7), ((0, 9), (0, 9)));
+        // filling the row to the edge puts the end at the start of the next
+        assert_eq!(renderer.layout("12345678", 7), ((0, 9), (1, 0)));
+        assert_eq!(renderer.layout("12345678", 8), ((1, 0), (1, 0)));
+        assert_eq!(renderer.layout("123456789", 9), ((1, 1), (1, 1)));
+        assert_eq!(renderer.layout("1234567890123456789", 18), ((2, 0), (2, 1)));
+        // a wide character that does not fit moves whole to the next row
+        assert_eq!(renderer.layout("1234567\u{754c}", 7), ((1, 0), (1, 2)));
+        assert_eq!(renderer.layout("123456\u{754c}", 8), ((1, 0), (1, 0)));
+        assert_eq!(renderer.layout("a\nb", 2), ((1, 0), (1, 1)));
+
+        // escape sequences take no room and a newline in the prompt starts a row
+        let renderer = Renderer::with_width("\x1b[32m~/src\x1b[0m\n$ ".to_string(), 10);
+        assert_eq!(renderer.layout("", 0), ((1, 2), (1, 2)));
+        assert_eq!(renderer.layout("12345678", 8), ((2, 0), (2, 0)));
+        // a terminal that reports no width gets 80 columns
+        let renderer = Renderer::with_width(String::new(), 0);
+        assert_eq!(renderer.layout(&"x".repeat(80), 79), ((0, 79), (1, 0)));
+    }
+
+    #[test]
+    fn set_options() {
+        use crate::commands::ShellOptions;
+        let set = |options: &mut ShellOptions, args: &str| {
+            let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+            options.apply(&args)
+        };
+        let mut options = ShellOptions::default();
+        assert_eq!(set(&mut options, "-e -u").unwrap(), None);
+        assert!(options.errexit && options.nounset && !options.xtrace);
+        set(&mut options, "-xC +e").unwrap();
+        assert!(options.xtrace && options.noclobber && !options.errexit);
+        set(&mut options, "-o errexit +o nounset +x").unwrap();
+        assert!(options.errexit && !options.nounset && !options.xtrace);
+        set(&mut options, "-euo pipefail").unwrap();
+        assert!(options.errexit && options.nounset && options.pipefail);
+        set(&mut options, "-o vi").unwrap();
+        assert!(options.vi);
+        set(&mut options, "-o emacs").unwrap();
+        assert!(!options.vi);
+
+        // a name-less -o or +o asks for the listing
+        assert_eq!(set(&mut options, "-o").unwrap(), Some(false));
+        assert_eq!(set(&mut options, "+C +o").unwrap(), Some(true));
+        assert!(!options.noclobber);
+
+        let error = |args: &str| set(&mut ShellOptions::default(), args).unwrap_err().to_string();
+        assert_eq!(error("-z"), "set: -z: invalid option");
+        assert_eq!(error("-o nosuch"), "set: nosuch: invalid option name");
+        assert_eq!(error("errexit"), "set: errexit: invalid option");
+
+        let options = ShellOptions { errexit: true, vi: true, ..ShellOptions::default() };
+        assert_eq!(
+            options.listing(false),
+            [
+                "emacs           off",
+                "errexit         on",
+                "noclobber       off",
+                "nounset         off",
+                "pipefail        off",
+                "vi              on",
+                "xtrace          off",
+            ]
+        );
+        assert_eq!(options.listing(true)[..3], ["set +o emacs", "set -o errexit", "set +o noclobber"]);
+        // the builtin turns errors into status 2
+        assert_eq!(run_builtin("set", &["-z"]), 2);
+        assert_eq!(run_builtin("set", &["-o", "nosuch"]), 2);
+    }
+
+    #[test]
+    fn rc_file_exit_ends_the_shell() {
+        use crate::interpreter::{ErrExit, Interpreter};
+        let dir = TempDir::new("rc-exit");
+        let rc = dir.join("cotshrc");
+        // a failing line is reported and the file goes on
+        std::fs::write(&rc, "cotsh-no-such-command\ncd nope\n").unwrap();
+        assert!(Interpreter::new(rc.clone()).unwrap().interpret_rc().is_ok());
+        std::fs::write(&rc, "cotsh-no-such-command\nexit 3\nexit 4\n").unwrap();
+        let err = Interpreter::new(rc).unwrap().interpret_rc().unwrap_err();
+        assert!(matches!(err.downcast_ref::<ErrExit>(), Some(ErrExit(3))));
+    }
+
+
+    #[test]
+    fn theme_parse() {
+        use crossterm::style::{Attribute, Color};
+        use crate::commands::Theme;
+        let style = Theme::parse("bold green on_#102030").unwrap();
+        assert!(style.attributes.has(Attribute::Bold));
+        assert_eq!(style.foreground_color, Some(Color::Green));
+        assert_eq!(style.background_color, Some(Color::Rgb { r: 0x10, g: 0x20, b: 0x30 }));
+        let style = Theme::parse("208 on_dark_grey underline").unwrap();
+        assert_eq!(style.foreground_color, Some(Color::AnsiValue(208)));
+        assert_eq!(style.background_color, Some(Color::DarkGrey));
+        assert!(style.attributes.has(Attribute::Underlined));
+        assert_eq!(Theme::parse("none").unwrap(), Default::default());
+        assert_eq!(Theme::parse("bold nosuchcolor").unwrap_err(), "nosuchcolor");
+        assert_eq!(Theme::parse("on_#12345").unwrap_err(), "on_#12345");
+    }
+
+    #[test]
+    fn highlight_leaves_hash_table_alone() {
+        use crossterm::style::Color;
+        use crate::commands::executable_cmds::ExecutableCmds;
+        use crate::COMMAND_HASH;
+        // a PATH command no other test runs through the shell
+        if ExecutableCmds::find_all("sleep").is_empty() {
+            return;
+        }
+        let spans = Highlighter::new().spans("sleep 1", 7, "");
+        assert_eq!(spans[0].0, "sleep");
+        assert_eq!(spans[0].1.foreground_color, Some(Color::Green));
+        assert!(COMMAND_HASH.lock().unwrap().peek("sleep").is_none());
+    }
+}
@@ -0,0 +1,32 @@
+use std::env;
+use std::path::PathBuf;
+
+pub struct Dirs;
+
+impl Dirs {
+    pub fn home() -> Option<PathBuf> {
+        match env::var("HOME") {
+            Ok(home) if !home.is_empty() => Some(PathBuf::from(home)),
+            _ => None,
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/cotsh`, falling back to `~/.config/cotsh`
+    pub fn config_dir() -> Option<PathBuf> {
+        Self::xdg_dir("XDG_CONFIG_HOME", ".config")
+    }
+
+    /// `$XDG_STATE_HOME/cotsh`, falling back to `~/.local/state/cotsh`
+    pub fn state_dir() -> Option<PathBuf> {
+        Self::xdg_dir("XDG_STATE_HOME", ".local/state")
+    }
+
+    fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+        let base = match env::var(var) {
+            // the spec says relative paths must be ignored
+            Ok(dir) if dir.starts_with('/') => PathBuf::from(dir),
+            _ => Self::home()?.join(fallback),
+        };
+        Some(base.join("cotsh"))
+    }
+}
@@ -0,0 +1,55 @@
+use unicode_width::UnicodeWidthStr;
+
+pub trait DisplayWidth {
+    /// How many terminal columns the text takes: wide characters count two,
+    /// combining marks none, and ANSI escape sequences are skipped
+    fn display_width(&self) -> usize;
+    /// The text without its ANSI escape sequences, as the terminal shows it
+    fn strip_escapes(&self) -> String;
+}
+
+impl DisplayWidth for str {
+    fn display_width(&self) -> usize {
+        self.strip_escapes().width()
+    }
+
+    fn strip_escapes(&self) -> String {
+        let mut plain = String::new();
+        let mut rest = self;
+        while let Some(start) = rest.find('\x1b') {
+            plain.push_str(&rest[..start]);
+            rest = skip_escape(&rest[start..]);
+        }
+        plain + rest
+    }
+}
+
+impl DisplayWidth for String {
+    fn display_width(&self) -> usize {
+        self.as_str().display_width()
+    }
+
+    fn strip_escapes(&self) -> String {
+        self.as_str().strip_escapes()
+    }
+}
+
+/// The text after the escape sequence `text` starts with: a CSI sequence
+/// like `\x1b[1;32m` up to its final letter, an OSC sequence like a window
+/// title up to BEL or ST, or else the escape and one character
+fn skip_escape(text: &str) -> &str {
+    let mut chars = text.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '[')) => chars
+            .find(|(_, char)| ('@'..='~').contains(char))
+            .map_or("", |(index, char)| &text[index + char.len_utf8()..]),
+        Some((_, ']')) => match (text.find('\x07'), text.find("\x1b\\")) {
+            (Some(bel), Some(st)) if st < bel => &text[st + 2..],
+            (Some(bel), _) => &text[bel + 1..],
+            (None, Some(st)) => &text[st + 2..],
+            (None, None) => "",
+        },
+        Some((index, char)) => &text[index + char.len_utf8()..],
+        None => "",
+    }
+}
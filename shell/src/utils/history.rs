@@ -0,0 +1,223 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::utils::Dirs;
+use crate::CURRENT_DIR;
+
+/// Used when `HISTSIZE` is unset or not a number, as in bash
+const DEFAULT_SIZE: usize = 500;
+
+/// A line entered at the prompt
+struct Entry {
+    line: String,
+    /// Where it ran and how it ended, when known. The history file keeps
+    /// them on a `#<status> <dir>` line before the entry.
+    dir: Option<PathBuf>,
+    status: Option<i32>,
+}
+
+/// Lines entered at the prompt, oldest first
+#[derive(Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    /// How many of the newest entries are not in the history file yet
+    unsaved: usize,
+}
+
+impl History {
+    /// `$XDG_STATE_HOME/cotsh/history`
+    fn file() -> Option<PathBuf> {
+        Dirs::state_dir().map(|dir| dir.join("history"))
+    }
+
+    /// `HISTSIZE`: how many entries are kept in memory
+    fn size() -> usize {
+        Self::limit("HISTSIZE").unwrap_or(DEFAULT_SIZE)
+    }
+
+    /// `HISTFILESIZE`: how many entries the history file keeps, `HISTSIZE` by default
+    fn file_size() -> usize {
+        Self::limit("HISTFILESIZE").unwrap_or_else(Self::size)
+    }
+
+    /// A negative limit means no limit
+    fn limit(var: &str) -> Option<usize> {
+        let limit: i64 = env::var(var).ok()?.trim().parse().ok()?;
+        Some(usize::try_from(limit).unwrap_or(usize::MAX))
+    }
+
+    /// Reads the history file, keeping the newest `HISTSIZE` entries
+    pub fn load(&mut self) {
+        let text = match Self::file().and_then(|file| fs::read_to_string(file).ok()) {
+            Some(text) => text,
+            None => return,
+        };
+        self.read(&text);
+    }
+
+    /// Replaces the entries with those in `text`, as a history file holds them
+    pub(crate) fn read(&mut self, text: &str) {
+        self.entries = Self::parse(text);
+        self.unsaved = 0;
+        self.trim(Self::size());
+    }
+
+    /// The entries in a history file. Files written before entries had a
+    /// `#<status> <dir>` line still read, as entries with neither. An entry
+    /// starting with `#` or `\` is written with a `\` in front, so a
+    /// comment typed at the prompt is never taken for that line.
+    fn parse(text: &str) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        let mut meta = None;
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let parsed = line.strip_prefix('#').and_then(|rest| rest.split_once(' ')).and_then(|(status, dir)| {
+                Some((status.parse::<i32>().ok()?, PathBuf::from(dir)))
+            });
+            match parsed {
+                Some(parsed) => meta = Some(parsed),
+                None => {
+                    let (status, dir) = meta.take().unzip();
+                    let line = line.strip_prefix('\\').unwrap_or(line).to_string();
+                    entries.push(Entry { line, dir, status });
+                }
+            }
+        }
+        entries
+    }
+
+    fn format(entries: &[Entry]) -> String {
+        let mut text = String::new();
+        for entry in entries {
+            if let (Some(status), Some(dir)) = (entry.status, &entry.dir) {
+                text.push_str(&format!("#{} {}\n", status, dir.display()));
+            }
+            if entry.line.starts_with(['#', '\\']) {
+                text.push('\\');
+            }
+            text.push_str(&format!("{}\n", entry.line));
+        }
+        text
+    }
+
+    /// Adds a line about to run in the shell's directory
+    pub fn push(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let dir = CURRENT_DIR.lock().unwrap().clone();
+        self.entries.push(Entry { line: line.to_string(), dir: Some(dir), status: None });
+        self.unsaved += 1;
+        self.trim(Self::size());
+    }
+
+    /// Records how the line just pushed ended
+    pub fn set_status(&mut self, status: i32) {
+        if self.unsaved == 0 {
+            return;
+        }
+        if let Some(entry) = self.entries.last_mut().filter(|entry| entry.status.is_none()) {
+            entry.status = Some(status);
+        }
+    }
+
+    fn trim(&mut self, size: usize) {
+        if self.entries.len() > size {
+            self.entries.drain(..self.entries.len() - size);
+        }
+        self.unsaved = self.unsaved.min(self.entries.len());
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&String> {
+        self.entries.get(index).map(|entry| &entry.line)
+    }
+
+    /// What the newest entry starting with `prefix` adds to it, skipping
+    /// entries that failed and preferring ones run in `dir`
+    pub fn suggest(&self, prefix: &str, dir: &Path) -> Option<&str> {
+        let candidates = || {
+            self.entries.iter().rev().filter(|entry| {
+                entry.line.len() > prefix.len() && entry.line.starts_with(prefix) && entry.status.is_none_or(|status| status == 0)
+            })
+        };
+        candidates()
+            .find(|entry| entry.dir.as_deref() == Some(dir))
+            .or_else(|| candidates().next())
+            .map(|entry| &entry.line[prefix.len()..])
+    }
+
+    /// The nearest entry containing `query`, starting at `from` and going
+    /// towards older entries when `reverse` is set. Gives its index and where
+    /// the query starts in it; lines equal to `skip` are passed over.
+    pub fn search(&self, query: &str, from: Option<usize>, reverse: bool, skip: Option<&str>) -> Option<(usize, usize)> {
+        let mut indices: Box<dyn Iterator<Item = usize>> = match (from, reverse) {
+            (Some(from), true) => Box::new((0..=from).rev()),
+            (Some(from), false) => Box::new(from..self.len()),
+            (None, _) => Box::new(std::iter::empty()),
+        };
+        indices.find_map(|index| {
+            let line = self.get(index)?;
+            if skip == Some(line.as_str()) {
+                return None;
+            }
+            line.find(query).map(|offset| (index, offset))
+        })
+    }
+
+    /// The match for `query` after the entry at `current`: the next older
+    /// one when `reverse` is set, else the next newer one. Without a current
+    /// entry the search starts at the newest or oldest entry. Entries with
+    /// the same line as the current one are skipped.
+    pub fn search_next(&self, query: &str, current: Option<usize>, reverse: bool) -> Option<(usize, usize)> {
+        let from = match (current, reverse) {
+            (Some(index), true) => index.checked_sub(1),
+            (Some(index), false) => Some(index + 1),
+            (None, true) => self.len().checked_sub(1),
+            (None, false) => Some(0),
+        };
+        let skip = current.and_then(|index| self.get(index)).map(String::as_str);
+        self.search(query, from, reverse, skip)
+    }
+
+    /// Appends the entries not in the history file yet. Called after every
+    /// line, so a closed terminal or a crash loses nothing.
+    pub fn append(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let file = match Self::file() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        if self.unsaved > 0 {
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut out = OpenOptions::new().create(true).append(true).open(&file)?;
+            out.write_all(Self::format(&self.entries[self.entries.len() - self.unsaved..]).as_bytes())?;
+            self.unsaved = 0;
+        }
+        Ok(())
+    }
+
+    /// Appends what is left, then cuts the file down to `HISTFILESIZE`
+    /// entries
+    pub fn save(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.append()?;
+        let file = match Self::file() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(_) => return Ok(()),
+        };
+        let entries = Self::parse(&text);
+        let size = Self::file_size();
+        if entries.len() > size {
+            fs::write(&file, Self::format(&entries[entries.len() - size..]))?;
+        }
+        Ok(())
+    }
+}
@@ -1,114 +1,381 @@
-use std::io::{self, Write};
+use std::time::Duration;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     terminal::{self, ClearType},
     cursor, execute, 
-    style::Print,
+    style::{ContentStyle, Print},
 };
 use crossterm::style::Stylize;
+use std::sync::atomic::Ordering;
+use crate::commands::{Completion, Highlighter, Traps};
+use crate::utils::{LineBuffer, Renderer, Signals, Tty, ViAction, ViMode};
+use crate::{CURRENT_DIR, HISTORY, LAST_STATUS, SHELL_OPTIONS};
+
+/// How an incremental history search ended
+enum Search {
+    /// Enter: run the line
+    Run(String),
+    /// Esc or a movement key: keep the line for editing
+    Edit(String),
+    /// Ctrl+G: back to the line as it was
+    Abort,
+    /// Ctrl+C
+    Interrupt,
+}
 
 pub trait Input {
-    fn read_line(msg: String) -> String;
+    /// Prints `msg` and reads a line with emacs-style editing, or vi-style
+    /// under `set -o vi`. `None` means end of input: Ctrl+D on an empty line.
+    /// Traps for signals that arrive meanwhile run right away; the error is
+    /// one of them ending the shell with `exit`.
+    fn read_line(msg: String) -> Result<Option<String>, Box<dyn std::error::Error>>;
 }
 
 impl Input for String {
-    fn read_line(msg: String) -> String {
-        let mut input = String::new();
-        let mut cursor_position = 0;
-        let msg_len = msg.len();
-        
-        // Print the prompt
-        execute!(io::stdout(), Print(msg.bold().green())).unwrap();
-        io::stdout().flush().unwrap();
+    fn read_line(msg: String) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let mut line = LineBuffer::new();
+        let prompt = msg.as_str().bold().green().to_string();
+        let mut renderer = Renderer::new(prompt.clone());
+        let mut highlighter = Highlighter::new();
+        // the history entry shown, and the line being typed before Up was pressed
+        let mut history_index: Option<usize> = None;
+        let mut draft = String::new();
+        // a second Tab in a row lists the candidates
+        let mut tabbed = false;
+        let mut vi = SHELL_OPTIONS.lock().unwrap().vi.then(ViMode::new);
         
         // Enable raw mode to read characters one by one
         terminal::enable_raw_mode().unwrap();
+
+        // Print the prompt
+        if let Some(vi) = &vi {
+            execute!(Tty, vi.cursor_style()).unwrap();
+        }
+        // what history or completion offers after the end of the line
+        let mut suggestion = redraw(&mut renderer, &mut highlighter, &line, true);
         
-        loop {
-            // Read a key event
-            if let Event::Key(KeyEvent { code, modifiers, .. }) = event::read().unwrap() {
-                match (code, modifiers) {
-                    // Handle Enter key - finish input
-                    (KeyCode::Enter, _) => {
-                        execute!(io::stdout(), Print("\r\n")).unwrap();
-                        break;
-                    },
-                    
-                    // Handle Ctrl+C - exit
-                    (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                        terminal::disable_raw_mode().unwrap();
-                        std::process::exit(0);
-                    },
-                    
-                    // Handle Backspace - delete a character
-                    (KeyCode::Backspace, _) => {
-                        if cursor_position > 0 {
-                            input.remove(cursor_position - 1);
-                            cursor_position -= 1;
-                            
-                            // Redraw the line
-                            execute!(
-                                io::stdout(),
-                                cursor::MoveToColumn(msg_len as u16),
-                                terminal::Clear(ClearType::FromCursorDown),
-                                Print(&input),
-                                cursor::MoveToColumn((msg_len + cursor_position) as u16)
-                            ).unwrap();
-                        }
-                    },
-                    
-                    // Handle Left arrow - move cursor left
-                    (KeyCode::Left, _) => {
-                        if cursor_position > 0 {
-                            cursor_position -= 1;
-                            execute!(
-                                io::stdout(),
-                                cursor::MoveLeft(1)
-                            ).unwrap();
-                        }
-                    },
-                    
-                    // Handle Right arrow - move cursor right
-                    (KeyCode::Right, _) => {
-                        if cursor_position < input.len() {
-                            cursor_position += 1;
-                            execute!(
-                                io::stdout(),
-                                cursor::MoveRight(1)
-                            ).unwrap();
-                        }
-                    },
-                    
-                    // Handle regular character input
-                    (KeyCode::Char(c), _) => {
-                        input.insert(cursor_position, c);
-                        cursor_position += 1;
-                        
-                        // Redraw the line
-                        execute!(
-                            io::stdout(),
-                            cursor::MoveToColumn(msg_len as u16),
-                            terminal::Clear(ClearType::FromCursorDown),
-                            Print(&input),
-                            cursor::MoveToColumn((msg_len + cursor_position) as u16)
-                        ).unwrap();
-                    },
-                    
-                    // Ignore other keys
-                    _ => {}
+        let input = loop {
+            // Read a key event, or run the traps for a signal that came first
+            let event = match read_event() {
+                Some(event) => event,
+                None => {
+                    renderer.finish("");
+                    if vi.is_some() {
+                        execute!(Tty, cursor::SetCursorStyle::DefaultUserShape).unwrap();
+                    }
+                    terminal::disable_raw_mode().unwrap();
+                    // an untrapped HUP or TERM ends the shell in here
+                    if let Err(err) = Traps::run_pending() {
+                        break Err(err);
+                    }
+                    terminal::enable_raw_mode().unwrap();
+                    if let Some(vi) = &vi {
+                        execute!(Tty, vi.cursor_style()).unwrap();
+                    }
+                    suggestion = redraw(&mut renderer, &mut highlighter, &line, true);
+                    continue;
                 }
+            };
+            let (code, modifiers) = match event {
+                Event::Key(KeyEvent { code, modifiers, .. }) => (code, modifiers),
+                Event::Resize(width, _) => {
+                    renderer.resize(width);
+                    suggestion = redraw(&mut renderer, &mut highlighter, &line, true);
+                    continue;
+                },
+                _ => continue,
+            };
+            // vi mode uses the key, or hands back one for the bindings below
+            let (code, modifiers) = match vi.as_mut().map(|vi| vi.key(code, modifiers, &mut line)) {
+                Some(ViAction::Handled) => {
+                    execute!(Tty, vi.as_ref().unwrap().cursor_style()).unwrap();
+                    suggestion = redraw(&mut renderer, &mut highlighter, &line, true);
+                    continue;
+                },
+                Some(ViAction::Key(code, modifiers)) => (code, modifiers),
+                None => (code, modifiers),
+            };
+            let second_tab = std::mem::take(&mut tabbed);
+            let alt = modifiers.contains(KeyModifiers::ALT);
+            match (code, modifiers) {
+                // Handle Enter key - finish input
+                (KeyCode::Enter, _) => {
+                    redraw(&mut renderer, &mut highlighter, &line, false);
+                    renderer.finish("");
+                    break Ok(Some(line.text().to_string()));
+                },
+                
+                // Handle Ctrl+C - discard the line and give a new prompt
+                (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                    redraw(&mut renderer, &mut highlighter, &line, false);
+                    renderer.finish("^C");
+                    LAST_STATUS.store(130, Ordering::SeqCst);
+                    break Ok(Some(String::new()));
+                },
+
+                // Handle Ctrl+D - end of input on an empty line, else delete
+                (KeyCode::Char('d'), KeyModifiers::CONTROL) if line.is_empty() => {
+                    renderer.finish("");
+                    break Ok(None);
+                },
+                (KeyCode::Char('d'), KeyModifiers::CONTROL) | (KeyCode::Delete, _) => line.delete(),
+                
+                // Handle Backspace - delete a character, or a word with Alt
+                (KeyCode::Backspace, _) if alt => line.kill_word_left(),
+                (KeyCode::Backspace, _) | (KeyCode::Char('h'), KeyModifiers::CONTROL) => line.backspace(),
+                
+                // Handle Right/End at the end of the line - take the
+                // suggestion, or its next word with Alt+F
+                (KeyCode::Right, _) | (KeyCode::End, _) | (KeyCode::Char('f'), KeyModifiers::CONTROL) | (KeyCode::Char('e'), KeyModifiers::CONTROL)
+                    if !suggestion.is_empty() => line.insert_str(&suggestion),
+                (KeyCode::Char('f'), _) if alt && !suggestion.is_empty() => line.insert_word(&suggestion),
+
+                // Handle cursor movement by character, word and line
+                (KeyCode::Left, _) | (KeyCode::Char('b'), KeyModifiers::CONTROL) => line.move_left(),
+                (KeyCode::Right, _) | (KeyCode::Char('f'), KeyModifiers::CONTROL) => line.move_right(),
+                (KeyCode::Home, _) | (KeyCode::Char('a'), KeyModifiers::CONTROL) => line.move_home(),
+                (KeyCode::End, _) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => line.move_end(),
+                (KeyCode::Char('b'), _) if alt => line.word_left(),
+                (KeyCode::Char('f'), _) if alt => line.word_right(),
+
+                // Handle kills and yanks
+                (KeyCode::Char('k'), KeyModifiers::CONTROL) => line.kill_to_end(),
+                (KeyCode::Char('u'), KeyModifiers::CONTROL) => line.kill_to_start(),
+                (KeyCode::Char('w'), KeyModifiers::CONTROL) => line.kill_blank_word(),
+                (KeyCode::Char('d'), _) if alt => line.kill_word_right(),
+                (KeyCode::Char('y'), KeyModifiers::CONTROL) => line.yank(),
+                (KeyCode::Char('y'), _) if alt => line.yank_pop(),
+
+                // Handle Ctrl+T - transpose characters
+                (KeyCode::Char('t'), KeyModifiers::CONTROL) => line.transpose(),
+
+                // Handle Ctrl+_ - undo; terminals send it as Ctrl+7
+                (KeyCode::Char('_'), KeyModifiers::CONTROL) | (KeyCode::Char('7'), KeyModifiers::CONTROL) => line.undo(),
+
+                // Handle Ctrl+L - clear the screen, keeping the line
+                (KeyCode::Char('l'), KeyModifiers::CONTROL) => {
+                    execute!(Tty, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0)).unwrap();
+                    renderer.reset();
+                },
+                
+                // Handle Up/Down arrows - walk the history, keeping the typed line
+                (KeyCode::Up, _) | (KeyCode::Down, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
+                    let up = code == KeyCode::Up || code == KeyCode::Char('p');
+                    let history = HISTORY.lock().unwrap();
+                    let next = match (up, history_index) {
+                        (true, None) => history.len().checked_sub(1),
+                        (true, Some(index)) => Some(index.saturating_sub(1)),
+                        (false, Some(index)) if index + 1 < history.len() => Some(index + 1),
+                        (false, Some(_)) => None,
+                        (false, None) => continue,
+                    };
+                    if history_index.is_none() && next.is_some() {
+                        draft = line.text().to_string();
+                    }
+                    match next {
+                        Some(index) => line.set(history.get(index).map(String::as_str).unwrap_or_default()),
+                        None if history_index.is_some() => line.set(&draft),
+                        None => continue,
+                    }
+                    history_index = next;
+                },
+
+                // Handle Ctrl+R/Ctrl+S - search the history backwards or forwards
+                (KeyCode::Char('r'), KeyModifiers::CONTROL) | (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+                    let found = search(code == KeyCode::Char('r'), line.text(), &mut renderer);
+                    // Put the prompt back in place of the search
+                    renderer.set_prompt(prompt.clone());
+                    match found {
+                        Search::Run(text) => {
+                            line.set(&text);
+                            redraw(&mut renderer, &mut highlighter, &line, false);
+                            renderer.finish("");
+                            break Ok(Some(text));
+                        },
+                        Search::Interrupt => {
+                            redraw(&mut renderer, &mut highlighter, &line, false);
+                            renderer.finish("^C");
+                            LAST_STATUS.store(130, Ordering::SeqCst);
+                            break Ok(Some(String::new()));
+                        },
+                        Search::Edit(text) => {
+                            line.set(&text);
+                            history_index = None;
+                        },
+                        Search::Abort => {},
+                    }
+                },
+
+                // Handle Tab - complete the word before the cursor
+                (KeyCode::Tab, _) => {
+                    tabbed = true;
+                    let completion = Completion::new(line.text(), line.cursor());
+                    if let Some(text) = completion.replacement() {
+                        line.replace_before_cursor(completion.start(), &text);
+                    } else if second_tab && completion.len() > 1 {
+                        let (width, _) = terminal::size().unwrap_or((80, 24));
+                        redraw(&mut renderer, &mut highlighter, &line, false);
+                        renderer.finish("");
+                        for row in completion.listing(width) {
+                            execute!(Tty, Print(row), Print("\r\n")).unwrap();
+                        }
+                    } else {
+                        continue;
+                    }
+                },
+
+                // Handle regular character input
+                (KeyCode::Char(c), modifiers) if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                    line.insert(c);
+                },
+                
+                // Ignore other keys
+                _ => continue,
             }
-        }
+
+            // Redraw the line
+            suggestion = redraw(&mut renderer, &mut highlighter, &line, true);
+        };
         
         // Disable raw mode when done
-        terminal::disable_raw_mode().unwrap();
-        
-        // If input is empty and we hit enter, we don't want to add a newline character
-        // as that might cause issues with command parsing
-        if input.is_empty() {
-            input = String::new();
+        if vi.is_some() {
+            execute!(Tty, cursor::SetCursorStyle::DefaultUserShape).unwrap();
         }
+        terminal::disable_raw_mode().unwrap();
         
         input
     }
 }
+
+/// Draws the line after the prompt, coloured, with the cursor in place.
+/// With `suggest` and the cursor at the end, also shows what `suggestion`
+/// offers after it, and returns that.
+fn redraw(renderer: &mut Renderer, highlighter: &mut Highlighter, line: &LineBuffer, suggest: bool) -> String {
+    let suggestion = match suggest && !line.is_empty() && line.cursor() == line.text().len() {
+        true => suggestion(line.text()),
+        false => String::new(),
+    };
+    renderer.render(&highlighter.spans(line.text(), line.cursor(), &suggestion), line.cursor());
+    suggestion
+}
+
+/// What the newest history entry that starts with `text` adds to it, or
+/// when there is none, what completing its last word adds
+fn suggestion(text: &str) -> String {
+    let dir = CURRENT_DIR.lock().unwrap().clone();
+    if let Some(rest) = HISTORY.lock().unwrap().suggest(text, &dir) {
+        return rest.to_string();
+    }
+    let completion = Completion::quiet(text, text.len());
+    completion
+        .replacement()
+        .and_then(|replacement| replacement.strip_prefix(&text[completion.start()..]).map(String::from))
+        .unwrap_or_default()
+}
+
+/// Searches the history for lines containing the typed query, showing
+/// The next terminal event, or `None` as soon as a caught signal is waiting
+/// for its trap
+fn read_event() -> Option<Event> {
+    loop {
+        if Signals::has_pending() {
+            return None;
+        }
+        if event::poll(Duration::from_millis(100)).unwrap() {
+            return Some(event::read().unwrap());
+        }
+    }
+}
+
+/// `(reverse-i-search)'query': match` in place of the prompt. Ctrl+R steps to
+/// older matches and Ctrl+S to newer ones.
+fn search(mut reverse: bool, original: &str, renderer: &mut Renderer) -> Search {
+    let history = HISTORY.lock().unwrap();
+    let mut query = String::new();
+    // the history entry matched, and where the query starts in it
+    let mut found: Option<(usize, usize)> = None;
+    let mut failed = false;
+
+    loop {
+        let line = match found {
+            Some((index, _)) => history.get(index).map(String::as_str).unwrap_or_default(),
+            None => original,
+        };
+        let label = format!(
+            "({}{}i-search)'{}': ",
+            if failed { "failed " } else { "" },
+            if reverse { "reverse-" } else { "" },
+            query
+        );
+        let (before, matched, after) = match found {
+            Some((_, offset)) if !query.is_empty() => {
+                (&line[..offset], &line[offset..offset + query.len()], &line[offset + query.len()..])
+            }
+            _ => (line, "", ""),
+        };
+        renderer.set_prompt(label);
+        let plain = ContentStyle::new();
+        renderer.render(&[(before, plain), (matched, plain.reverse()), (after, plain)], before.len());
+
+        let (code, modifiers) = match read_event() {
+            Some(Event::Key(KeyEvent { code, modifiers, .. })) => (code, modifiers),
+            Some(Event::Resize(width, _)) => {
+                renderer.resize(width);
+                continue;
+            },
+            // the line editor runs the traps
+            None => return Search::Edit(line.to_string()),
+            _ => continue,
+        };
+        let line = line.to_string();
+        let newest = history.len().checked_sub(1);
+        match (code, modifiers) {
+            (KeyCode::Enter, _) => return Search::Run(line),
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Search::Interrupt,
+            (KeyCode::Char('g'), KeyModifiers::CONTROL) => return Search::Abort,
+
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) | (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+                reverse = code == KeyCode::Char('r');
+                if query.is_empty() {
+                    continue;
+                }
+                match history.search_next(&query, found.map(|(index, _)| index), reverse) {
+                    Some(next) => {
+                        found = Some(next);
+                        failed = false;
+                    }
+                    None => failed = true,
+                }
+            },
+
+            (KeyCode::Backspace, _) => {
+                query.pop();
+                found = match query.is_empty() {
+                    true => None,
+                    false => history.search(&query, newest, true, None),
+                };
+                failed = !query.is_empty() && found.is_none();
+            },
+
+            (KeyCode::Char(c), modifiers) if !modifiers.contains(KeyModifiers::CONTROL) => {
+                query.push(c);
+                // the current match may still contain the longer query
+                let from = found.map(|(index, _)| index).or(newest);
+                match history.search(&query, from, reverse, None) {
+                    Some(next) => {
+                        found = Some(next);
+                        failed = false;
+                    }
+                    None => failed = true,
+                }
+            },
+
+            (KeyCode::Esc, _) | (KeyCode::Left, _) | (KeyCode::Right, _) | (KeyCode::Up, _)
+            | (KeyCode::Down, _) | (KeyCode::Home, _) | (KeyCode::End, _) | (KeyCode::Tab, _) => {
+                return Search::Edit(line);
+            },
+
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::io;
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when the last foreground command was killed by SIGINT, or when
+/// Ctrl-C reached the interactive shell itself
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set by `init`. Only an interactive shell on a terminal does job control.
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Runs external commands in their own process group and hands them the
+/// terminal, so Ctrl-C and Ctrl-Z reach the command but not the shell.
+/// Non-interactive shells leave their commands in the shell's own group,
+/// so a signal sent to it reaches everything the shell started.
+pub struct JobControl;
+
+#[cfg(unix)]
+impl JobControl {
+    /// Called once by an interactive shell. The shell has to ignore SIGTTOU
+    /// to take the terminal back after a command ran in the foreground, and
+    /// catches SIGINT so a Ctrl-C while a builtin, a script or the startup
+    /// files run stops them instead of the shell.
+    pub fn init() {
+        INTERACTIVE.store(true, Ordering::SeqCst);
+        // SAFETY: installing SIG_IGN has no preconditions
+        unsafe {
+            libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        }
+        Self::catch_interrupt();
+    }
+
+    /// The interactive shell's own SIGINT handler, also put back when an
+    /// INT trap is removed
+    pub fn catch_interrupt() {
+        // SAFETY: the handler only stores to an atomic
+        unsafe {
+            libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+        }
+    }
+
+    /// Whether commands get their own process group and the terminal
+    fn controls_terminal() -> bool {
+        Self::is_interactive() && Self::has_terminal()
+    }
+
+    fn has_terminal() -> bool {
+        // SAFETY: isatty only inspects the descriptor
+        unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+    }
+
+    pub fn prepare(cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+        if !Self::controls_terminal() {
+            return;
+        }
+        cmd.process_group(0);
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            cmd.pre_exec(|| {
+                // the parent does the same; whichever runs first wins the race
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpid());
+                libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+                libc::signal(libc::SIGINT, libc::SIG_DFL);
+                Ok(())
+            });
+        }
+    }
+
+    /// For `exec`: the new program must not inherit the dispositions the
+    /// interactive shell set for itself
+    pub fn prepare_exec(cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: only async-signal-safe calls before exec
+        unsafe {
+            cmd.pre_exec(|| {
+                libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+                libc::signal(libc::SIGINT, libc::SIG_DFL);
+                Ok(())
+            });
+        }
+    }
+
+    /// Waits for a command started with `prepare` and returns its status,
+    /// 128 + signal when it was killed or stopped by a signal
+    pub fn wait(child: Child) -> io::Result<i32> {
+        let pid = child.id() as libc::pid_t;
+        let has_terminal = Self::controls_terminal();
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        // SAFETY: plain syscalls on our own child and terminal
+        unsafe {
+            if has_terminal {
+                libc::tcsetpgrp(libc::STDIN_FILENO, pid);
+            }
+            let res = Self::wait_pid(pid);
+            if has_terminal {
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            }
+            res
+        }
+    }
+
+    unsafe fn wait_pid(pid: libc::pid_t) -> io::Result<i32> {
+        let mut status = 0;
+        loop {
+            // SAFETY: status outlives the call
+            if unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) } == -1 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            if libc::WIFEXITED(status) {
+                return Ok(libc::WEXITSTATUS(status));
+            }
+            if libc::WIFSIGNALED(status) {
+                let signal = libc::WTERMSIG(status);
+                if signal == libc::SIGINT {
+                    INTERRUPTED.store(true, Ordering::SeqCst);
+                    // the terminal echoed ^C; finish that line
+                    println!();
+                }
+                return Ok(128 + signal);
+            }
+            if libc::WIFSTOPPED(status) {
+                // there is no `fg`/`bg` yet, so a stopped command is left
+                // behind and the prompt comes back
+                eprintln!("\ncotsh: [{}] stopped", pid);
+                return Ok(128 + libc::WSTOPSIG(status));
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl JobControl {
+    pub fn init() {
+        INTERACTIVE.store(true, Ordering::SeqCst);
+    }
+
+    pub fn catch_interrupt() {}
+
+    pub fn prepare(_cmd: &mut Command) {}
+
+    pub fn prepare_exec(_cmd: &mut Command) {}
+
+    pub fn wait(mut child: Child) -> io::Result<i32> {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        Ok(child.wait()?.code().unwrap_or(1))
+    }
+}
+
+impl JobControl {
+    pub fn is_interactive() -> bool {
+        INTERACTIVE.load(Ordering::SeqCst)
+    }
+
+    /// Whether the last foreground command was interrupted with Ctrl-C. The
+    /// flag stays set until the next command is waited for or it is cleared,
+    /// so nested scripts and lists can all unwind.
+    pub fn was_interrupted() -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+
+    pub fn clear_interrupted() {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,326 @@
+use std::env;
+use std::sync::Mutex;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Killed text, newest last. Shared by every line so a kill can be yanked
+/// into a later command.
+static KILL_RING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// How many kills the ring keeps, as in readline
+const KILL_RING_SIZE: usize = 10;
+
+/// What the previous edit was, for the edits that behave differently
+/// right after another one
+#[derive(Clone, Copy, PartialEq)]
+enum Last {
+    Other,
+    /// Typing, undone as one step
+    Insert,
+    /// Killing, added to the same kill ring entry
+    Kill,
+    /// Yanking, which Alt-Y replaces; the yanked range and ring index
+    Yank { start: usize, end: usize, index: usize },
+}
+
+/// The line being edited: its text, the cursor as a byte offset at the start
+/// of a grapheme cluster, and the undo history. Moving and deleting go by
+/// grapheme, so an accented letter or an emoji is one step.
+pub struct LineBuffer {
+    text: String,
+    cursor: usize,
+    undo: Vec<(String, usize)>,
+    last: Last,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self { text: String::new(), cursor: 0, undo: Vec::new(), last: Last::Other }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.last = Last::Other;
+        self.cursor = cursor.min(self.text.len());
+    }
+
+    /// Characters that are part of a word besides letters and digits, from
+    /// `$WORDCHARS` as in zsh. Alt-B, Alt-F and Alt-D stop at anything else.
+    /// A grapheme is judged by its first character, so marks combined with
+    /// a letter stay part of the word.
+    fn is_word_char(grapheme: &str, word_chars: &str) -> bool {
+        grapheme.chars().next().is_some_and(|char| char.is_alphanumeric() || word_chars.contains(char))
+    }
+
+    /// The start of the grapheme before `from`
+    pub fn prev_boundary(&self, from: usize) -> usize {
+        self.text[..from].grapheme_indices(true).next_back().map_or(0, |(index, _)| index)
+    }
+
+    /// The end of the grapheme starting at `from`
+    pub fn next_boundary(&self, from: usize) -> usize {
+        self.text[from..].graphemes(true).next().map_or(from, |grapheme| from + grapheme.len())
+    }
+
+    /// The start of the word before `from`, skipping what separates them
+    fn word_start(&self, from: usize) -> usize {
+        let word_chars = env::var("WORDCHARS").unwrap_or_default();
+        let before = &self.text[..from];
+        let end = before
+            .grapheme_indices(true)
+            .rev()
+            .find(|(_, grapheme)| Self::is_word_char(grapheme, &word_chars))
+            .map_or(0, |(index, grapheme)| index + grapheme.len());
+        before[..end]
+            .grapheme_indices(true)
+            .rev()
+            .find(|(_, grapheme)| !Self::is_word_char(grapheme, &word_chars))
+            .map_or(0, |(index, grapheme)| index + grapheme.len())
+    }
+
+    /// The end of the word after `from`, skipping what separates them
+    fn word_end(&self, from: usize) -> usize {
+        let word_chars = env::var("WORDCHARS").unwrap_or_default();
+        let after = &self.text[from..];
+        let start = after
+            .grapheme_indices(true)
+            .find(|(_, grapheme)| Self::is_word_char(grapheme, &word_chars))
+            .map_or(after.len(), |(index, _)| index);
+        after[start..]
+            .grapheme_indices(true)
+            .find(|(_, grapheme)| !Self::is_word_char(grapheme, &word_chars))
+            .map_or(self.text.len(), |(index, _)| from + start + index)
+    }
+
+    /// Saves the line for undo. A run of typed characters is one step.
+    fn snapshot(&mut self, action: Last) {
+        if !(action == Last::Insert && self.last == Last::Insert) {
+            self.undo.push((self.text.clone(), self.cursor));
+        }
+        self.last = action;
+    }
+
+    /// Replaces the whole line, as recalling history does
+    pub fn set(&mut self, text: &str) {
+        self.snapshot(Last::Other);
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    pub fn insert(&mut self, char: char) {
+        self.snapshot(Last::Insert);
+        self.text.insert(self.cursor, char);
+        self.cursor += char.len_utf8();
+    }
+
+    /// Puts `text` in place of `start..cursor`, as completion does
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        self.snapshot(Last::Other);
+        self.text.replace_range(start..self.cursor, text);
+        self.cursor = start + text.len();
+    }
+
+    /// Inserts `text` at the cursor and moves past it
+    pub fn insert_str(&mut self, text: &str) {
+        self.snapshot(Last::Other);
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    /// Inserts the start of `text` up to the end of its first word, as
+    /// Alt-F takes one word of a suggestion
+    pub fn insert_word(&mut self, text: &str) {
+        let joined = Self { text: format!("{}{}", &self.text[..self.cursor], text), ..Self::new() };
+        let end = joined.word_end(self.cursor);
+        self.insert_str(&joined.text[self.cursor..end]);
+    }
+
+    /// Removes `start..end` without touching the kill ring, leaving the
+    /// cursor at `start`
+    pub fn remove(&mut self, start: usize, end: usize) -> String {
+        self.snapshot(Last::Other);
+        self.cursor = start;
+        self.text.drain(start..end).collect()
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.snapshot(Last::Other);
+            let start = self.prev_boundary(self.cursor);
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    /// Deletes the grapheme under the cursor
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.snapshot(Last::Other);
+            let end = self.next_boundary(self.cursor);
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.last = Last::Other;
+        self.cursor = self.prev_boundary(self.cursor);
+    }
+
+    pub fn move_right(&mut self) {
+        self.last = Last::Other;
+        self.cursor = self.next_boundary(self.cursor);
+    }
+
+    pub fn move_home(&mut self) {
+        self.last = Last::Other;
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.last = Last::Other;
+        self.cursor = self.text.len();
+    }
+
+    pub fn word_left(&mut self) {
+        self.last = Last::Other;
+        self.cursor = self.word_start(self.cursor);
+    }
+
+    pub fn word_right(&mut self) {
+        self.last = Last::Other;
+        self.cursor = self.word_end(self.cursor);
+    }
+
+    /// Removes `start..end` into the kill ring. Kills in a row build one
+    /// entry, so Ctrl-Y brings them all back.
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        let joined = self.last == Last::Kill;
+        self.snapshot(Last::Kill);
+        let killed: String = self.text.drain(start..end).collect();
+        let mut ring = KILL_RING.lock().unwrap();
+        match ring.last_mut() {
+            Some(last) if joined && start < self.cursor => last.insert_str(0, &killed),
+            Some(last) if joined => last.push_str(&killed),
+            _ => {
+                ring.push(killed);
+                if ring.len() > KILL_RING_SIZE {
+                    ring.remove(0);
+                }
+            }
+        }
+        self.cursor = start;
+    }
+
+    /// Ctrl-K
+    pub fn kill_to_end(&mut self) {
+        self.kill(self.cursor, self.text.len());
+    }
+
+    /// Ctrl-U
+    pub fn kill_to_start(&mut self) {
+        self.kill(0, self.cursor);
+    }
+
+    /// Ctrl-W: back to the previous blank, whatever `$WORDCHARS` says
+    pub fn kill_blank_word(&mut self) {
+        let before = self.text[..self.cursor].trim_end();
+        let start = before
+            .char_indices()
+            .rfind(|(_, char)| char.is_whitespace())
+            .map_or(0, |(index, char)| index + char.len_utf8());
+        self.kill(start, self.cursor);
+    }
+
+    /// Alt-Backspace
+    pub fn kill_word_left(&mut self) {
+        self.kill(self.word_start(self.cursor), self.cursor);
+    }
+
+    /// Alt-D
+    pub fn kill_word_right(&mut self) {
+        self.kill(self.cursor, self.word_end(self.cursor));
+    }
+
+    /// Ctrl-Y: inserts the newest kill
+    pub fn yank(&mut self) {
+        let ring = KILL_RING.lock().unwrap();
+        let (index, text) = match ring.last() {
+            Some(text) => (ring.len() - 1, text.clone()),
+            None => return,
+        };
+        drop(ring);
+        self.snapshot(Last::Other);
+        let start = self.cursor;
+        self.text.insert_str(start, &text);
+        self.cursor = start + text.len();
+        self.last = Last::Yank { start, end: self.cursor, index };
+    }
+
+    /// Alt-Y: right after a yank, swaps the yanked text for the kill before it
+    pub fn yank_pop(&mut self) {
+        let (start, end, index) = match self.last {
+            Last::Yank { start, end, index } => (start, end, index),
+            _ => return,
+        };
+        let ring = KILL_RING.lock().unwrap();
+        if ring.len() < 2 {
+            return;
+        }
+        let index = index.checked_sub(1).unwrap_or(ring.len() - 1);
+        let text = ring[index].clone();
+        drop(ring);
+        self.undo.push((self.text.clone(), self.cursor));
+        self.text.replace_range(start..end, &text);
+        self.cursor = start + text.len();
+        self.last = Last::Yank { start, end: self.cursor, index };
+    }
+
+    /// Ctrl-T: swaps the graphemes around the cursor, or the last two at the
+    /// end of the line, and moves past them
+    pub fn transpose(&mut self) {
+        if self.cursor == 0 || self.text.graphemes(true).nth(1).is_none() {
+            return;
+        }
+        self.snapshot(Last::Other);
+        if self.cursor == self.text.len() {
+            self.cursor = self.prev_boundary(self.cursor);
+        }
+        let start = self.prev_boundary(self.cursor);
+        let end = self.next_boundary(self.cursor);
+        let (first, second) = self.text[start..end].split_at(self.cursor - start);
+        let swapped = format!("{}{}", second, first);
+        self.text.replace_range(start..end, &swapped);
+        self.cursor = end;
+    }
+
+    /// How many undo steps there are, to pass to `squash_undo` later
+    pub fn undo_mark(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Makes every edit since `mark` one undo step
+    pub fn squash_undo(&mut self, mark: usize) {
+        self.undo.truncate(mark + 1);
+    }
+
+    /// Ctrl-_
+    pub fn undo(&mut self) {
+        if let Some((text, cursor)) = self.undo.pop() {
+            self.text = text;
+            self.cursor = cursor;
+        }
+        self.last = Last::Other;
+    }
+}
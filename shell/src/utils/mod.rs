@@ -3,8 +3,26 @@ mod word_splitter;
 mod debug_tool;
 mod result_printer;
 mod macros;
+mod dirs;
+mod job_control;
+mod signals;
+mod history;
+mod line_buffer;
+mod vi_mode;
+mod display_width;
+mod renderer;
+mod tty;
 
 pub use debug_tool::DebugPrint;
 pub use result_printer::ResultPrinter;
 pub use word_splitter::WordSplitter;
-pub use input::Input;
\ No newline at end of file
+pub use input::Input;
+pub use dirs::Dirs;
+pub use job_control::JobControl;
+pub use signals::Signals;
+pub use history::History;
+pub use line_buffer::LineBuffer;
+pub use vi_mode::{ViAction, ViMode};
+pub use display_width::DisplayWidth;
+pub use renderer::Renderer;
+pub use tty::Tty;
\ No newline at end of file
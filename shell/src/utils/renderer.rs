@@ -0,0 +1,230 @@
+use std::io::Write;
+use crossterm::{
+    cursor, queue,
+    style::{ContentStyle, Print},
+    terminal::{self, ClearType},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::utils::{DisplayWidth, Tty};
+
+/// A row and a column on screen, counted from where the prompt starts
+type Position = (usize, usize);
+
+/// One grapheme of the line as it was drawn
+#[derive(PartialEq)]
+struct Cell {
+    grapheme: String,
+    style: ContentStyle,
+}
+
+/// Draws the prompt and the line being edited over as many rows as they
+/// take, and remembers what is on screen so a redraw only prints from the
+/// first change
+pub struct Renderer {
+    /// Printed as it is, escape sequences and all
+    prompt: String,
+    /// Whether the prompt is on screen; when not, the next render prints it
+    /// at the cursor, which should be at the start of a row
+    prompted: bool,
+    cells: Vec<Cell>,
+    /// Where the terminal cursor is, and the byte of the line it is at
+    cursor: Position,
+    at: usize,
+    width: usize,
+}
+
+impl Renderer {
+    pub fn new(prompt: String) -> Self {
+        let (width, _) = terminal::size().unwrap_or((0, 0));
+        Self::with_width(prompt, width)
+    }
+
+    /// A renderer for a terminal `width` columns wide, with nothing drawn yet
+    pub fn with_width(prompt: String, width: u16) -> Self {
+        Self { prompt, prompted: false, cells: Vec::new(), cursor: (0, 0), at: 0, width: Self::usable(width) }
+    }
+
+    /// A terminal that reports no width gets the usual 80 columns
+    fn usable(width: u16) -> usize {
+        match width {
+            0 => 80,
+            width => width as usize,
+        }
+    }
+
+    /// Where `grapheme` goes when the previous one ended at `at`, and where
+    /// it ends. A wide grapheme that does not fit moves to the next row, as
+    /// the terminal moves it.
+    fn place(&self, (row, column): Position, grapheme: &str) -> (Position, Position) {
+        if grapheme == "\n" || grapheme == "\r\n" {
+            return ((row, column), (row + 1, 0));
+        }
+        let width = grapheme.width();
+        let start = match column + width > self.width {
+            true => (row + 1, 0),
+            false => (row, column),
+        };
+        let end = match start.1 + width {
+            column if column >= self.width => (start.0 + 1, 0),
+            column => (start.0, column),
+        };
+        (start, end)
+    }
+
+    fn prompt_end(&self) -> Position {
+        let plain = self.prompt.strip_escapes();
+        plain.graphemes(true).fold((0, 0), |at, grapheme| self.place(at, grapheme).1)
+    }
+
+    /// Where the cell holding byte `at` of the line starts, or where the line
+    /// ends when `at` is past it
+    fn position(&self, cells: &[Cell], at: usize) -> Position {
+        let (mut position, mut offset) = (self.prompt_end(), 0);
+        for cell in cells {
+            let (start, end) = self.place(position, &cell.grapheme);
+            if offset >= at {
+                return start;
+            }
+            position = end;
+            offset += cell.grapheme.len();
+        }
+        position
+    }
+
+    /// Where `cells` end when they follow the prompt
+    fn end(&self, cells: &[Cell]) -> Position {
+        cells.iter().fold(self.prompt_end(), |at, cell| self.place(at, &cell.grapheme).1)
+    }
+
+    /// Where `render` would put the cursor for byte `at` of `line`, and
+    /// where the line would end
+    #[cfg(test)]
+    pub(crate) fn layout(&self, line: &str, at: usize) -> (Position, Position) {
+        let style = ContentStyle::new();
+        let cells: Vec<Cell> = line.graphemes(true).map(|grapheme| Cell { grapheme: grapheme.to_string(), style }).collect();
+        (self.position(&cells, at), self.end(&cells))
+    }
+
+    fn move_to(&mut self, out: &mut Tty, (row, column): Position) {
+        if (row, column) == self.cursor {
+            return;
+        }
+        if row < self.cursor.0 {
+            queue!(out, cursor::MoveUp((self.cursor.0 - row) as u16)).unwrap();
+        } else if row > self.cursor.0 {
+            queue!(out, cursor::MoveDown((row - self.cursor.0) as u16)).unwrap();
+        }
+        queue!(out, cursor::MoveToColumn(column as u16)).unwrap();
+        self.cursor = (row, column);
+    }
+
+    /// Prints `text`, laid out as `plain`. When it fills its last row to the
+    /// edge the terminal holds the cursor there until the next character, so
+    /// a newline puts it at the start of the next row, where it is counted.
+    fn print(&mut self, out: &mut Tty, text: &str, plain: &str) {
+        let mut edge = false;
+        for grapheme in plain.graphemes(true) {
+            let (_, end) = self.place(self.cursor, grapheme);
+            if grapheme.ends_with('\n') {
+                edge = false;
+            } else if grapheme.width() > 0 {
+                edge = end.1 == 0;
+            }
+            self.cursor = end;
+        }
+        queue!(out, Print(text)).unwrap();
+        if edge {
+            queue!(out, Print("\r\n")).unwrap();
+        }
+    }
+
+    /// Draws the line, given as pieces of text with their styles, with the
+    /// cursor at byte `at` of it
+    pub fn render(&mut self, spans: &[(&str, ContentStyle)], at: usize) {
+        let mut out = Tty;
+        let cells: Vec<Cell> = spans
+            .iter()
+            .flat_map(|(text, style)| {
+                text.graphemes(true).map(|grapheme| Cell { grapheme: grapheme.to_string(), style: *style })
+            })
+            .collect();
+        if !self.prompted {
+            self.cursor = (0, 0);
+            let prompt = self.prompt.clone();
+            self.print(&mut out, &prompt.replace('\n', "\r\n"), &prompt.strip_escapes());
+            self.prompted = true;
+            self.cells.clear();
+        }
+
+        let same = self.cells.iter().zip(&cells).take_while(|(old, new)| old == new).count();
+        if same < self.cells.len() || same < cells.len() {
+            let from = self.end(&cells[..same]);
+            self.move_to(&mut out, from);
+            // typing at the end only adds to what is there
+            if same < self.cells.len() {
+                queue!(out, terminal::Clear(ClearType::FromCursorDown)).unwrap();
+            }
+            for run in cells[same..].chunk_by(|a, b| a.style == b.style) {
+                let text: String = run.iter().map(|cell| cell.grapheme.as_str()).collect();
+                let styled = run[0].style.apply(text.as_str()).to_string();
+                self.print(&mut out, &styled, &text);
+            }
+        }
+        self.cells = cells;
+        self.at = at;
+        let position = self.position(&self.cells, at);
+        self.move_to(&mut out, position);
+        out.flush().unwrap();
+    }
+
+    /// Shows `prompt` in place of the current one, as the history search does
+    pub fn set_prompt(&mut self, prompt: String) {
+        if prompt == self.prompt {
+            return;
+        }
+        if self.prompted {
+            let mut out = Tty;
+            self.move_to(&mut out, (0, 0));
+            queue!(out, terminal::Clear(ClearType::FromCursorDown)).unwrap();
+            self.prompted = false;
+        }
+        self.prompt = prompt;
+    }
+
+    /// The terminal is `width` columns wide now. Terminals rewrap the rows
+    /// they show, so this finds where the prompt starts at the new width,
+    /// clears from there, and leaves the next render to draw it all again.
+    pub fn resize(&mut self, width: u16) {
+        self.width = Self::usable(width);
+        if !self.prompted {
+            return;
+        }
+        let mut out = Tty;
+        self.cursor = self.position(&self.cells, self.at);
+        self.move_to(&mut out, (0, 0));
+        queue!(out, terminal::Clear(ClearType::FromCursorDown)).unwrap();
+        self.prompted = false;
+    }
+
+    /// Forgets what is on screen, after it was cleared
+    pub fn reset(&mut self) {
+        self.prompted = false;
+    }
+
+    /// Moves past the end of the line, prints `mark` such as `^C`, and starts
+    /// a new row. The next render draws the prompt again.
+    pub fn finish(&mut self, mark: &str) {
+        let mut out = Tty;
+        if self.prompted {
+            let end = self.end(&self.cells);
+            self.move_to(&mut out, end);
+        }
+        // a line that fills its last row has already moved to a new one
+        if self.cursor.1 != 0 || self.cursor.0 == 0 || !mark.is_empty() {
+            queue!(out, Print(mark), Print("\r\n")).unwrap();
+        }
+        out.flush().unwrap();
+        self.prompted = false;
+    }
+}
@@ -1,10 +1,21 @@
+use std::io::{self, IsTerminal};
 use crossterm::style::Stylize;
+use crate::interpreter::Interpreter;
 
 pub struct ResultPrinter;
 
 impl ResultPrinter {
+    /// Prints an error, at the script line it came from when a script runs
     pub fn error<T: std::fmt::Display>(error: T) {
-        println!("{}", format!("{}", error).dark_red())
+        Self::located_error(Interpreter::located(&error.to_string()))
+    }
+
+    /// Prints an error that already says where it happened
+    pub fn located_error<T: std::fmt::Display>(error: T) {
+        match io::stderr().is_terminal() {
+            true => eprintln!("{}", format!("{}", error).dark_red()),
+            false => eprintln!("{}", error),
+        }
     }
 
     pub fn success<T: std::fmt::Display>(success: T)  {
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Signals that can be trapped, by name and number
+#[cfg(unix)]
+const SIGNALS: [(&str, i32); 7] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+];
+#[cfg(not(unix))]
+const SIGNALS: [(&str, i32); 2] = [("INT", 2), ("TERM", 15)];
+
+const MAX_SIGNAL: usize = 32;
+
+/// Signals caught since the last `take_pending`, indexed by number
+static PENDING: [AtomicBool; MAX_SIGNAL] = [const { AtomicBool::new(false) }; MAX_SIGNAL];
+
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+    if let Some(pending) = PENDING.get(signal as usize) {
+        pending.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Thin wrapper over signal dispositions. Caught signals only set a flag;
+/// the interpreter runs their handlers later, at a point where that is safe.
+pub struct Signals;
+
+impl Signals {
+    /// Accepts `INT`, `SIGINT`, `int` or `2`
+    pub fn number(name: &str) -> Option<i32> {
+        if let Ok(number) = name.parse::<i32>() {
+            return SIGNALS.iter().find(|(_, n)| *n == number).map(|(_, n)| *n);
+        }
+        let name = name.to_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        SIGNALS.iter().find(|(n, _)| *n == name).map(|(_, n)| *n)
+    }
+
+    pub fn name(number: i32) -> Option<&'static str> {
+        SIGNALS.iter().find(|(_, n)| *n == number).map(|(name, _)| *name)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        SIGNALS.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Whether a signal was caught since the last `take_pending`
+    pub fn has_pending() -> bool {
+        PENDING.iter().any(|pending| pending.load(Ordering::SeqCst))
+    }
+
+    /// Signals caught since the last call, lowest number first
+    pub fn take_pending() -> Vec<i32> {
+        (0..MAX_SIGNAL)
+            .filter(|&signal| PENDING[signal].swap(false, Ordering::SeqCst))
+            .map(|signal| signal as i32)
+            .collect()
+    }
+}
+
+#[cfg(unix)]
+impl Signals {
+    pub fn catch(signal: i32) {
+        // SAFETY: the handler only stores to an atomic
+        unsafe {
+            libc::signal(signal, on_signal as *const () as libc::sighandler_t);
+        }
+    }
+
+    pub fn ignore(signal: i32) {
+        // SAFETY: installing SIG_IGN has no preconditions
+        unsafe {
+            libc::signal(signal, libc::SIG_IGN);
+        }
+    }
+
+    pub fn reset(signal: i32) {
+        // SAFETY: installing SIG_DFL has no preconditions
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+
+    /// Sends the signal to the shell itself
+    pub fn raise(signal: i32) {
+        // SAFETY: raise has no preconditions
+        unsafe {
+            libc::raise(signal);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl Signals {
+    pub fn catch(_signal: i32) {}
+
+    pub fn ignore(_signal: i32) {}
+
+    pub fn reset(_signal: i32) {}
+
+    pub fn raise(_signal: i32) {}
+}
@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+/// A copy of the shell's stdout from before any `exec >file`
+static SAVED: OnceLock<File> = OnceLock::new();
+
+/// Where the prompt and the line being edited are drawn. `exec >file`
+/// moves stdout for the commands that follow, but the editor stays on the
+/// terminal it started on.
+pub struct Tty;
+
+impl Tty {
+    /// Keeps a copy of stdout above the descriptors `exec` hands out. Call
+    /// it once, before the first command runs.
+    #[cfg(unix)]
+    pub fn save() {
+        use std::os::fd::FromRawFd;
+        // SAFETY: the copy is a new descriptor, owned by the `File` from here
+        unsafe {
+            let fd = libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 10);
+            if fd != -1 {
+                let _ = SAVED.set(File::from_raw_fd(fd));
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn save() {}
+}
+
+/// Writes to the saved copy, or to stdout when there is none
+impl Write for Tty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match SAVED.get() {
+            Some(mut file) => {
+                // what a command left in stdout's buffer comes first
+                let _ = io::stdout().flush();
+                file.write(buf)
+            }
+            None => io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match SAVED.get() {
+            Some(mut file) => file.flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
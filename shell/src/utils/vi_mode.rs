@@ -0,0 +1,450 @@
+use std::sync::Mutex;
+use crossterm::cursor::SetCursorStyle;
+use crossterm::event::{KeyCode, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::utils::LineBuffer;
+
+/// Text deleted or yanked in normal mode, put back with p and P. Shared by
+/// every line like the kill ring.
+static REGISTER: Mutex<String> = Mutex::new(String::new());
+
+/// Larger counts are taken as this one. No line needs more, and a huge `p`
+/// or `u` count would run for a long time.
+const MAX_COUNT: usize = 10_000;
+
+/// What `read_line` should do with a key after vi mode has seen it
+pub enum ViAction {
+    /// The key was used
+    Handled,
+    /// Handle this key the way emacs mode does
+    Key(KeyCode, KeyModifiers),
+}
+
+/// A motion and how many times to make it. `arg` is the character f, t, F
+/// and T look for.
+#[derive(Clone, Copy)]
+struct Motion {
+    kind: char,
+    arg: Option<char>,
+    count: usize,
+}
+
+/// A complete normal mode command
+enum Command {
+    Move(Motion),
+    /// d, c or y over a motion
+    Operator(char, Motion),
+    /// dd, cc or yy: the line is all there is, so counts don't matter
+    Line(char),
+    /// i, a, I or A
+    Insert(char),
+    /// x X D C s S p P u and `.`, with an explicit count if there was one
+    Simple(char, Option<usize>),
+    /// r and the replacement
+    Replace(char, usize),
+    /// Keys emacs mode already handles: k and j walk the history, / and ?
+    /// search it
+    Key(KeyCode, KeyModifiers),
+}
+
+enum Parse {
+    Complete(Command),
+    Incomplete,
+    Invalid,
+}
+
+/// The last change, for `.`: its normal mode keys and the text typed after them
+#[derive(Clone)]
+struct Change {
+    keys: Vec<char>,
+    inserted: String,
+}
+
+/// Modal editing for `set -o vi`. Lines start in insert mode, where keys
+/// work as in emacs mode; Esc switches to normal mode.
+pub struct ViMode {
+    insert: bool,
+    /// The normal mode command typed so far
+    keys: Vec<char>,
+    last_change: Option<Change>,
+    /// The change whose text is being typed in insert mode
+    recording: Option<Change>,
+    /// The undo step the change being typed started at
+    undo_mark: usize,
+    /// The last f, t, F or T and its character, for ; and ,
+    last_find: Option<(char, char)>,
+}
+
+impl ViMode {
+    pub fn new() -> Self {
+        Self { insert: true, keys: Vec::new(), last_change: None, recording: None, undo_mark: 0, last_find: None }
+    }
+
+    /// A bar in insert mode and a block in normal mode
+    pub fn cursor_style(&self) -> SetCursorStyle {
+        match self.insert {
+            true => SetCursorStyle::SteadyBar,
+            false => SetCursorStyle::SteadyBlock,
+        }
+    }
+
+    pub fn key(&mut self, code: KeyCode, modifiers: KeyModifiers, line: &mut LineBuffer) -> ViAction {
+        let plain = !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        if self.insert {
+            return match code {
+                KeyCode::Esc => {
+                    self.normal_mode(line);
+                    ViAction::Handled
+                }
+                // Esc and a key typed quickly after it arrive as Alt and the key
+                KeyCode::Char(char) if modifiers.contains(KeyModifiers::ALT) => {
+                    self.normal_mode(line);
+                    self.normal_key(char, line)
+                }
+                KeyCode::Char(char) if plain => {
+                    if let Some(change) = self.recording.as_mut() {
+                        change.inserted.push(char);
+                    }
+                    ViAction::Key(code, modifiers)
+                }
+                KeyCode::Backspace => {
+                    if let Some(change) = self.recording.as_mut() {
+                        change.inserted.pop();
+                    }
+                    ViAction::Key(code, modifiers)
+                }
+                _ => ViAction::Key(code, modifiers),
+            };
+        }
+        match code {
+            KeyCode::Char(char) if plain => self.normal_key(char, line),
+            KeyCode::Backspace => self.normal_key('h', line),
+            KeyCode::Esc => {
+                self.keys.clear();
+                ViAction::Handled
+            }
+            _ => {
+                self.keys.clear();
+                ViAction::Key(code, modifiers)
+            }
+        }
+    }
+
+    fn normal_mode(&mut self, line: &mut LineBuffer) {
+        self.insert = false;
+        if let Some(change) = self.recording.take() {
+            self.last_change = Some(change);
+            line.squash_undo(self.undo_mark);
+        }
+        line.set_cursor(line.prev_boundary(line.cursor()));
+    }
+
+    fn normal_key(&mut self, char: char, line: &mut LineBuffer) -> ViAction {
+        self.keys.push(char);
+        let command = match Self::parse(&self.keys) {
+            Parse::Complete(command) => command,
+            Parse::Incomplete => return ViAction::Handled,
+            Parse::Invalid => {
+                self.keys.clear();
+                return ViAction::Handled;
+            }
+        };
+        let keys = std::mem::take(&mut self.keys);
+        let is_change = match &command {
+            Command::Operator(op, _) | Command::Line(op) => *op != 'y',
+            Command::Insert(_) | Command::Replace(_, _) => true,
+            Command::Simple(char, _) => !matches!(char, 'u' | '.'),
+            Command::Move(_) | Command::Key(_, _) => false,
+        };
+        let mark = line.undo_mark();
+        let action = self.run(command, line);
+        if is_change && self.insert {
+            self.recording = Some(Change { keys, inserted: String::new() });
+            self.undo_mark = mark;
+        } else if is_change {
+            self.last_change = Some(Change { keys, inserted: String::new() });
+            line.squash_undo(mark);
+        }
+        if !self.insert && line.cursor() == line.text().len() {
+            line.set_cursor(line.prev_boundary(line.cursor()));
+        }
+        action
+    }
+
+    /// Reads a count: digits that don't start with 0, which is a motion
+    fn count(keys: &[char], index: &mut usize) -> Option<usize> {
+        let start = *index;
+        while keys.get(*index).is_some_and(|key| key.is_ascii_digit() && (*index > start || *key != '0')) {
+            *index += 1;
+        }
+        let digits: String = keys[start..*index].iter().collect();
+        match digits.is_empty() {
+            true => None,
+            false => Some(digits.parse().unwrap_or(MAX_COUNT).min(MAX_COUNT)),
+        }
+    }
+
+    fn parse(keys: &[char]) -> Parse {
+        let mut index = 0;
+        let count = Self::count(keys, &mut index);
+        let key = match keys.get(index) {
+            Some(key) => *key,
+            None => return Parse::Incomplete,
+        };
+        let rest = &keys[index + 1..];
+        let command = match key {
+            'd' | 'c' | 'y' => {
+                let mut index = 0;
+                let inner = Self::count(rest, &mut index);
+                let total = count.unwrap_or(1).saturating_mul(inner.unwrap_or(1)).min(MAX_COUNT);
+                match rest.get(index) {
+                    None => return Parse::Incomplete,
+                    Some(op) if *op == key => Command::Line(key),
+                    Some(kind) => match Self::motion(*kind, rest.get(index + 1).copied(), total) {
+                        Parse::Complete(Command::Move(motion)) => Command::Operator(key, motion),
+                        other => return other,
+                    },
+                }
+            }
+            'r' => match rest.first() {
+                Some(char) => Command::Replace(*char, count.unwrap_or(1)),
+                None => return Parse::Incomplete,
+            },
+            'i' | 'a' | 'I' | 'A' => Command::Insert(key),
+            'x' | 'X' | 'D' | 'C' | 's' | 'S' | 'p' | 'P' | 'u' | '.' => Command::Simple(key, count),
+            'k' => Command::Key(KeyCode::Up, KeyModifiers::NONE),
+            'j' => Command::Key(KeyCode::Down, KeyModifiers::NONE),
+            '/' => Command::Key(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            '?' => Command::Key(KeyCode::Char('s'), KeyModifiers::CONTROL),
+            kind => return Self::motion(kind, rest.first().copied(), count.unwrap_or(1)),
+        };
+        Parse::Complete(command)
+    }
+
+    fn motion(kind: char, arg: Option<char>, count: usize) -> Parse {
+        match kind {
+            'h' | 'l' | 'w' | 'b' | 'e' | '0' | '^' | '$' | ';' | ',' => {
+                Parse::Complete(Command::Move(Motion { kind, arg: None, count }))
+            }
+            'f' | 't' | 'F' | 'T' => match arg {
+                Some(arg) => Parse::Complete(Command::Move(Motion { kind, arg: Some(arg), count })),
+                None => Parse::Incomplete,
+            },
+            _ => Parse::Invalid,
+        }
+    }
+
+    /// Whether a character is blank, part of a word, or punctuation; vi
+    /// words are runs of one class
+    fn class(char: char) -> u8 {
+        match char {
+            char if char.is_whitespace() => 0,
+            char if char.is_alphanumeric() || char == '_' => 1,
+            _ => 2,
+        }
+    }
+
+    /// Where `motion` goes from the cursor, as a grapheme index, and whether
+    /// an operator includes the grapheme there. `chars` holds the first
+    /// character of each grapheme.
+    fn target(&mut self, chars: &[char], pos: usize, motion: Motion) -> Option<(usize, bool)> {
+        let len = chars.len();
+        let class = |index: usize| chars.get(index).map_or(0, |char| Self::class(*char));
+        let mut target = pos;
+        let mut inclusive = false;
+        // ; and , skip a match right next to the cursor, where t and T stopped
+        let repeat = matches!(motion.kind, ';' | ',');
+        let (kind, arg) = match (motion.kind, self.last_find) {
+            (';', Some(find)) => find,
+            (',', Some((kind, arg))) => {
+                let reversed = match kind {
+                    'f' => 'F',
+                    'F' => 'f',
+                    't' => 'T',
+                    _ => 't',
+                };
+                (reversed, arg)
+            }
+            (';' | ',', None) => return None,
+            (kind, _) => (kind, motion.arg.unwrap_or_default()),
+        };
+        for _ in 0..motion.count {
+            let from = target;
+            target = match kind {
+                'h' => target.saturating_sub(1),
+                'l' => (target + 1).min(len),
+                '0' => 0,
+                '^' => chars.iter().position(|char| !char.is_whitespace()).unwrap_or(0),
+                '$' => len,
+                'w' => {
+                    let mut next = target;
+                    let start = class(next);
+                    while next < len && start != 0 && class(next) == start {
+                        next += 1;
+                    }
+                    while next < len && class(next) == 0 {
+                        next += 1;
+                    }
+                    next
+                }
+                'b' => {
+                    let mut prev = target;
+                    while prev > 0 && class(prev - 1) == 0 {
+                        prev -= 1;
+                    }
+                    let start = class(prev.saturating_sub(1));
+                    while prev > 0 && class(prev - 1) == start {
+                        prev -= 1;
+                    }
+                    prev
+                }
+                'e' => {
+                    inclusive = true;
+                    let mut next = target + 1;
+                    while next < len && class(next) == 0 {
+                        next += 1;
+                    }
+                    let start = class(next);
+                    while next + 1 < len && class(next + 1) == start {
+                        next += 1;
+                    }
+                    next.min(len.saturating_sub(1))
+                }
+                'f' | 't' => {
+                    inclusive = true;
+                    let skip = if kind == 't' && repeat { 2 } else { 1 };
+                    let found = (target + skip..len).find(|index| chars[*index] == arg)?;
+                    if kind == 't' { found - 1 } else { found }
+                }
+                'F' | 'T' => {
+                    let skip = if kind == 'T' && repeat { 1 } else { 0 };
+                    let found = (0..target.saturating_sub(skip)).rev().find(|index| chars[*index] == arg)?;
+                    if kind == 'T' { found + 1 } else { found }
+                }
+                _ => return None,
+            };
+            // at either end of the line: the rest of the count changes nothing
+            if target == from {
+                break;
+            }
+        }
+        if matches!(motion.kind, 'f' | 't' | 'F' | 'T') {
+            self.last_find = Some((kind, arg));
+        }
+        Some((target, inclusive))
+    }
+
+    fn run(&mut self, command: Command, line: &mut LineBuffer) -> ViAction {
+        let graphemes: Vec<(usize, &str)> = line.text().grapheme_indices(true).collect();
+        let chars: Vec<char> = graphemes.iter().filter_map(|(_, grapheme)| grapheme.chars().next()).collect();
+        let offsets: Vec<usize> = graphemes.iter().map(|(index, _)| *index).collect();
+        let len = line.text().len();
+        let byte = move |index: usize| offsets.get(index).copied().unwrap_or(len);
+        let pos = line.text()[..line.cursor()].graphemes(true).count();
+        match command {
+            Command::Move(motion) => {
+                if let Some((target, _)) = self.target(&chars, pos, motion) {
+                    line.set_cursor(byte(target));
+                }
+            }
+            Command::Operator(op, motion) => {
+                // cw changes to the end of the word, like ce
+                let motion = match (op, motion.kind) {
+                    ('c', 'w') if chars.get(pos).is_some_and(|char| !char.is_whitespace()) => Motion { kind: 'e', ..motion },
+                    _ => motion,
+                };
+                let (target, inclusive) = match self.target(&chars, pos, motion) {
+                    Some(target) => target,
+                    None => return ViAction::Handled,
+                };
+                let (start, end) = match target < pos {
+                    true => (target, pos),
+                    false => (pos, (target + inclusive as usize).min(chars.len())),
+                };
+                self.apply(op, byte(start), byte(end), line);
+            }
+            Command::Line(op) => self.apply(op, 0, line.text().len(), line),
+            Command::Insert(kind) => {
+                match kind {
+                    'a' => line.set_cursor(line.next_boundary(line.cursor())),
+                    'I' => line.set_cursor(0),
+                    'A' => line.set_cursor(line.text().len()),
+                    _ => {}
+                }
+                self.insert = true;
+            }
+            Command::Replace(char, count) => {
+                if let Some(end) = pos.checked_add(count).filter(|end| *end <= chars.len()) {
+                    line.remove(byte(pos), byte(end));
+                    line.insert_str(&char.to_string().repeat(count));
+                    line.set_cursor(byte(end - 1));
+                }
+            }
+            Command::Simple('.', count) => self.repeat(count, line),
+            Command::Simple(key, count) => {
+                let times = count.unwrap_or(1);
+                let motion = |kind| Motion { kind, arg: None, count: times };
+                let command = match key {
+                    'x' => Command::Operator('d', motion('l')),
+                    'X' => Command::Operator('d', motion('h')),
+                    'D' => Command::Operator('d', motion('$')),
+                    'C' => Command::Operator('c', motion('$')),
+                    's' => Command::Operator('c', motion('l')),
+                    'S' => Command::Line('c'),
+                    'u' => {
+                        (0..times).for_each(|_| line.undo());
+                        return ViAction::Handled;
+                    }
+                    _ => {
+                        let text = REGISTER.lock().unwrap().repeat(times);
+                        if key == 'p' && !line.is_empty() {
+                            line.set_cursor(line.next_boundary(line.cursor()));
+                        }
+                        line.insert_str(&text);
+                        line.set_cursor(line.prev_boundary(line.cursor()));
+                        return ViAction::Handled;
+                    }
+                };
+                return self.run(command, line);
+            }
+            Command::Key(code, modifiers) => return ViAction::Key(code, modifiers),
+        }
+        ViAction::Handled
+    }
+
+    fn apply(&mut self, op: char, start: usize, end: usize, line: &mut LineBuffer) {
+        match op {
+            'y' => {
+                *REGISTER.lock().unwrap() = line.text()[start..end].to_string();
+                line.set_cursor(start);
+            }
+            _ => {
+                *REGISTER.lock().unwrap() = line.remove(start, end);
+                self.insert = op == 'c';
+            }
+        }
+    }
+
+    /// `.`: runs the last change again, with `count` in place of its own
+    fn repeat(&mut self, count: Option<usize>, line: &mut LineBuffer) {
+        let change = match self.last_change.clone() {
+            Some(change) => change,
+            None => return,
+        };
+        let mark = line.undo_mark();
+        let mut keys: Vec<char> = change.keys.iter().copied().skip_while(char::is_ascii_digit).collect();
+        if let Some(count) = count {
+            keys.splice(0..0, count.to_string().chars());
+        }
+        if let Parse::Complete(command) = Self::parse(&keys) {
+            self.run(command, line);
+        }
+        if self.insert {
+            line.insert_str(&change.inserted);
+            self.insert = false;
+            line.set_cursor(line.prev_boundary(line.cursor()));
+        }
+        line.squash_undo(mark);
+        self.last_change = Some(change);
+    }
+}
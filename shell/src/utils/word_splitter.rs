@@ -6,6 +6,7 @@ pub trait WordSplitter {
     fn split_double_quote(&self) -> Vec<String>;
     fn split_path(&self) -> Output;
     fn advance_split(&self) -> Vec<String>;
+    fn unquote(&self) -> String;
 }
 
 impl WordSplitter for String {
@@ -184,4 +185,38 @@ impl WordSplitter for String {
 
         words
     }
+
+    fn unquote(&self) -> String {
+        // Removes quotes the way the shell does for a final word: single
+        // quotes keep everything literally, a backslash escapes the next
+        // character outside single quotes
+        let mut word = String::new();
+        let mut quote_char: Option<char> = None;
+        let mut chars = self.chars();
+
+        while let Some(ch) = chars.next() {
+            match (ch, quote_char) {
+                ('\\', Some('\'')) => word.push(ch),
+                ('\\', Some('"')) => match chars.next() {
+                    // inside double quotes only a few characters can be escaped
+                    Some(next @ ('"' | '\\' | '$' | '`')) => word.push(next),
+                    Some(next) => {
+                        word.push('\\');
+                        word.push(next);
+                    }
+                    None => word.push('\\'),
+                },
+                ('\\', None) => {
+                    if let Some(next) = chars.next() {
+                        word.push(next);
+                    }
+                }
+                ('\'' | '"', None) => quote_char = Some(ch),
+                (c, Some(q)) if c == q => quote_char = None,
+                _ => word.push(ch),
+            }
+        }
+
+        word
+    }
 }
@@ -0,0 +1,95 @@
+#![cfg(unix)]
+
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// A config dir holding one plugin, `cotsh-<name>`, that runs `script`
+fn config_dir(test: &str, name: &str, script: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cotsh-plugin-{}-{}", test, std::process::id()));
+    let plugins = dir.join("cotsh/plugins");
+    std::fs::create_dir_all(&plugins).unwrap();
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    let path = plugins.join(format!("cotsh-{}", name));
+    std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    dir
+}
+
+/// Runs the shell in `dir` with its plugins and `input` piped into it
+fn run(dir: &Path, input: &str, env: &[(&str, String)]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cotsh"))
+        .arg("--norc")
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    output
+}
+
+#[test]
+fn output_error_and_status() {
+    let dir = config_dir("output", "greet", r#"cat > /dev/null
+printf '{"output": "hello %s\\n", "error": "careful\\n", "status": 3}' "$1"
+"#);
+    let output = run(&dir, "greet world\necho $?\n", &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello world\n3\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "careful\n");
+}
+
+#[test]
+fn set_and_cd() {
+    let dir = config_dir("set", "move", r#"cat > /dev/null
+echo '{"cd": "sub", "set": {"MOVED": "yes", "GONE": null}}'
+"#);
+    let output = run(&dir, "move\necho $MOVED-$GONE\npwd\n", &[("GONE", "still here".to_string())]);
+    let sub = dir.join("sub").canonicalize().unwrap_or(dir.join("sub"));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), format!("yes-\n{}\n", sub.display()));
+}
+
+#[test]
+fn exit_code_without_a_response() {
+    let dir = config_dir("silent", "quiet", "exit 4\n");
+    let output = run(&dir, "quiet\necho $?\n", &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "4\n");
+}
+
+#[test]
+fn malformed_response() {
+    let dir = config_dir("malformed", "broken", "cat > /dev/null\necho 'not json'\n");
+    let output = run(&dir, "broken\necho $?\n", &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("cotsh: broken: invalid plugin response"));
+}
+
+#[test]
+fn large_request_and_response() {
+    // the plugin answers before reading a request larger than a pipe buffer
+    let dir = config_dir("large", "big", r#"printf '{"output": "'
+head -c 200000 /dev/zero | tr '\0' a
+printf '\\n"}'
+cat > /dev/null
+"#);
+    let output = run(&dir, "big\n", &[("COTSH_BIG", "b".repeat(100_000))]);
+    assert_eq!(output.stdout.len(), 200_001);
+}
+
+#[test]
+fn path_change_empties_the_hash_table() {
+    let dir = config_dir("repath", "repath", r#"cat > /dev/null
+echo '{"set": {"PATH": "/usr/bin:/bin"}}'
+"#);
+    let input = "hash -p /bin/sh fake\nhash -t fake\nrepath\nhash -t fake\n";
+    let output = run(&dir, input, &[("PATH", "/bin:/usr/bin".to_string())]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "/bin/sh\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("hash: fake: not found"));
+    assert_eq!(output.status.code(), Some(1));
+}
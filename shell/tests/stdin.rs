@@ -0,0 +1,196 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the shell with `input` piped into it
+fn run(input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cotsh"))
+        .arg("--norc")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn runs_piped_lines() {
+    let output = run("echo one\n# a comment\n\necho two\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\ntwo\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn exits_with_last_status() {
+    let output = run("echo one\nfalse\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\n");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run("false\ntrue\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn stops_at_end_of_input() {
+    // no banner for empty input, and a last line without a newline still runs
+    let output = run("");
+    assert!(output.stdout.is_empty());
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = run("echo last");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "last\n");
+}
+
+#[test]
+fn exit_stops_reading() {
+    let output = run("exit 3\necho unreachable\n");
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("unreachable"));
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn commands_stay_in_the_callers_group() {
+    // the fifth field of /proc/self/stat is the process group
+    let output = run("cat /proc/self/stat\n");
+    let stat = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stat.rsplit(')').next().unwrap().split_whitespace().collect();
+    // SAFETY: getpgrp has no preconditions
+    assert_eq!(fields[2].parse::<i32>().unwrap(), unsafe { libc::getpgrp() });
+}
+
+#[cfg(unix)]
+#[test]
+fn script_errors_are_located() {
+    use std::os::unix::fs::PermissionsExt;
+    let script = std::env::temp_dir().join(format!("cotsh-located-{}", std::process::id()));
+    std::fs::write(&script, "echo ok; nosuchcmd arg\nset -u\necho \"$COTSH_UNSET_VAR\"\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let output = run(&format!("{}\n", script.display()));
+    std::fs::remove_file(&script).unwrap();
+
+    // stderr is a pipe, so there are no colours either
+    let name = script.display();
+    let expected = format!(
+        "{name}:1:10: command not found: nosuchcmd\n 1 | echo ok; nosuchcmd arg\n   |          ^^^^^^^^^\n\
+         {name}:3:7: COTSH_UNSET_VAR: unbound variable\n 3 | echo \"$COTSH_UNSET_VAR\"\n   |       ^^^^^^^^^^^^^^^^\n"
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected);
+}
+
+#[cfg(unix)]
+#[test]
+fn exit_trap_runs_on_term() {
+    use std::os::unix::process::ExitStatusExt;
+    let output = run("trap 'echo cleanup' EXIT\nkill -TERM $$\necho after\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "cleanup\n");
+    assert_eq!(output.status.signal(), Some(libc::SIGTERM));
+}
+
+#[test]
+fn exec_redirects_later_commands() {
+    let dir = std::env::temp_dir().join(format!("cotsh-stdin-exec-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let out = dir.join("out");
+    let input = format!("echo before\nexec >{}\necho after\nexec 2>&1\nnosuchcommand\n", out.display());
+    let output = run(&input);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    assert!(output.stderr.is_empty());
+    let text = std::fs::read_to_string(&out).unwrap();
+    assert!(text.starts_with("after\n") && text.contains("nosuchcommand"));
+    assert_eq!(output.status.code(), Some(127));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn exec_rejects_descriptors_it_never_opened() {
+    // 3 to 9 hold placeholders inside the shell, but are still closed to the user
+    let output = run("exec 5>&3\necho $?\nexec 4>/dev/null\nexec 5>&4\necho $?\nexec 4>&-\nexec 5>&4\necho $?\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n0\n1\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("exec: 3: bad file descriptor"));
+    assert!(stderr.contains("exec: 4: bad file descriptor"));
+}
+
+#[test]
+fn command_skips_aliases() {
+    let output = run("alias echo='echo aliased'\necho hi\ncommand echo hi\ncommand -v echo\ncommand -V echo\ncommand -V cotsh-nope\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "aliased hi\nhi\necho\necho is a shell builtin\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("command: cotsh-nope: not found"));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn command_prints_and_describes_like_bash() {
+    // -v prints the name of a builtin and the path of anything else; -V says what it is
+    let output = run("command -v echo\ncommand -v sh\ncommand -V echo\ncommand -V sh\ncommand -v cotsh-nope\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "echo");
+    assert!(lines[1].starts_with('/') && lines[1].ends_with("/sh"));
+    assert_eq!(lines[2], "echo is a shell builtin");
+    assert_eq!(lines[3], format!("sh is {}", lines[1]));
+    // -v says nothing about a name it cannot find
+    assert!(output.stderr.is_empty());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn type_describes_every_match() {
+    // where `sh` is on this machine's PATH, in search order
+    let paths: Vec<String> = std::env::split_paths(&std::env::var("PATH").unwrap())
+        .map(|dir| dir.join("sh"))
+        .filter(|path| path.is_file())
+        .map(|path| path.display().to_string())
+        .collect();
+    let output = run("type -p sh\nalias sh='sh -e'\ntype sh\ntype -t sh echo\ntype -p sh\ntype -a sh\ntype -t nope\nwhich echo\n");
+    // `-p` prints nothing for a name that runs as an alias
+    let mut expected = format!("{}\nsh is aliased to `sh -e'\nalias\nbuiltin\nsh is aliased to `sh -e'\n", paths[0]);
+    for path in &paths {
+        expected.push_str(&format!("sh is {}\n", path));
+    }
+    expected.push_str("echo: shell builtin\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn complete_prints_definitions_it_reads_back() {
+    let defined = "complete -o nospace -o plusdirs -a \"build it's \\$HOME a\\\\b\" -c cargo\ncomplete -f -F make-targets make\ncomplete -d cd\n";
+    let output = run(&format!("{}complete -p\n", defined));
+    let printed = String::from_utf8_lossy(&output.stdout).to_string();
+    assert_eq!(
+        printed,
+        "complete -o nospace -o plusdirs -a \"build it's \\$HOME a\\\\b\" cargo\ncomplete -d cd\ncomplete -f -F make-targets make\n"
+    );
+    // the printed lines define the same completions again
+    let output = run(&format!("{}complete -p\n", printed));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), printed);
+
+    let output = run(&format!("{}complete -r cargo\ncomplete -p\ncomplete -r\ncomplete -p\ncomplete -p cd\n", defined));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "complete -d cd\ncomplete -f -F make-targets make\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("complete: cd: no completion specification"));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn highlight_prints_what_it_reads_back() {
+    let output = run("highlight command bold #00ff00\nhighlight -p command\nhighlight -r command\nhighlight -p command\nhighlight error nosuchcolor\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "highlight command bold #00ff00\nhighlight command green\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("highlight: nosuchcolor: invalid style"));
+
+    // the whole theme as printed is a script that sets it again
+    let printed = String::from_utf8(run("highlight string italic on_236\nhighlight\n").stdout).unwrap();
+    assert!(printed.contains("highlight string italic on_236\n"));
+    let output = run(&format!("highlight -r\n{}highlight\n", printed));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), printed);
+    assert_eq!(output.status.code(), Some(0));
+}